@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::spec;
+
+/// A `%addon` section of the kickstart.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Addon {
+    name: String,
+    options: Vec<String>,
+    body: Vec<String>,
+}
+
+impl FromStr for Addon {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut addon = Addon::new("");
+        for (key, value) in spec::parse_spec(s)? {
+            match key.as_str() {
+                "name" => addon.name = spec::required_value(&key, value)?,
+                "option" => addon.options.push(spec::required_value(&key, value)?),
+                "line" => addon.body.push(spec::required_value(&key, value)?),
+                _ => return Err(anyhow::anyhow!("Unknown addon option: {key}")),
+            }
+        }
+        if addon.name.is_empty()
+            || !addon
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(anyhow::anyhow!("Invalid addon name: {}", addon.name));
+        }
+        if addon.body.iter().any(|x| x.trim() == "%end") {
+            return Err(anyhow::anyhow!(
+                "Addon {} must not contain %end",
+                addon.name
+            ));
+        }
+        Ok(addon)
+    }
+}
+
+impl Addon {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn new(name: &str) -> Self {
+        Addon {
+            name: name.to_string(),
+            options: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn option(mut self, option: impl Into<String>) -> Self {
+        self.options.push(option.into());
+        self
+    }
+
+    pub fn line(mut self, line: impl Into<String>) -> Self {
+        self.body.push(line.into());
+        self
+    }
+
+    pub fn generate(&self) -> String {
+        let mut header = format!("%addon {}", self.name);
+        for option in &self.options {
+            header.push(' ');
+            header.push_str(option);
+        }
+        format!("{header}\n{}\n%end", self.body.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_addon() {
+        assert_eq!(
+            Addon::new("com_redhat_kdump")
+                .option("--enable")
+                .option("--reserve-mb='auto'")
+                .generate(),
+            "%addon com_redhat_kdump --enable --reserve-mb='auto'\n\n%end"
+        );
+        assert_eq!(
+            r#"name=com_example_addon,option=--enable,line="key = a, b""#
+                .parse::<Addon>()
+                .unwrap()
+                .generate(),
+            "%addon com_example_addon --enable\nkey = a, b\n%end"
+        );
+        assert!("option=--enable".parse::<Addon>().is_err());
+        assert!("name=bad-name".parse::<Addon>().is_err());
+        assert!("name=a,line=%end".parse::<Addon>().is_err());
+    }
+}
@@ -1,11 +1,91 @@
 use clap::Args;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::str::FromStr;
+
+use crate::spec;
 
 fn disk_default() -> u32 {
     70
 }
 
+const DISK_BUSES: &[&str] = &["virtio", "sata", "scsi", "usb", "ide"];
+const DISK_FORMATS: &[&str] = &["qcow2", "raw"];
+const DEFAULT_OSINFO: &str = "almalinux8";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VmDisk {
+    size: u32,
+    bus: Option<String>,
+    format: Option<String>,
+    pool: Option<String>,
+}
+
+impl FromStr for VmDisk {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut disk = VmDisk {
+            size: 0,
+            bus: None,
+            format: None,
+            pool: None,
+        };
+        for (key, value) in spec::parse_spec(s)? {
+            match key.as_str() {
+                "size" => {
+                    let value = spec::required_value(&key, value)?;
+                    disk.size = value
+                        .parse()
+                        .map_err(|_| anyhow::anyhow!("Invalid disk size: {value}"))?;
+                }
+                "bus" => {
+                    let value = spec::required_value(&key, value)?;
+                    if !DISK_BUSES.contains(&value.as_str()) {
+                        return Err(anyhow::anyhow!(
+                            "Invalid disk bus: {value} (expected one of {})",
+                            DISK_BUSES.join(", ")
+                        ));
+                    }
+                    disk.bus = Some(value);
+                }
+                "format" => {
+                    let value = spec::required_value(&key, value)?;
+                    if !DISK_FORMATS.contains(&value.as_str()) {
+                        return Err(anyhow::anyhow!(
+                            "Invalid disk format: {value} (expected one of {})",
+                            DISK_FORMATS.join(", ")
+                        ));
+                    }
+                    disk.format = Some(value);
+                }
+                "pool" => disk.pool = Some(spec::required_value(&key, value)?),
+                _ => return Err(anyhow::anyhow!("Unknown disk option: {key}")),
+            }
+        }
+        if disk.size == 0 {
+            return Err(anyhow::anyhow!("Disk size is not set"));
+        }
+        Ok(disk)
+    }
+}
+
+impl VmDisk {
+    fn virt_install_arg(&self) -> String {
+        let mut arg = format!("size={}", self.size);
+        if let Some(bus) = &self.bus {
+            arg.push_str(&format!(",bus={bus}"));
+        }
+        if let Some(format) = &self.format {
+            arg.push_str(&format!(",format={format}"));
+        }
+        if let Some(pool) = &self.pool {
+            arg.push_str(&format!(",pool={pool}"));
+        }
+        arg
+    }
+}
+
 #[derive(Args, Debug, Clone, Serialize, Deserialize, Default)]
 pub struct CreateVmBase {
     #[arg(long, short = 'n', help = "Virtual Machine Name")]
@@ -13,6 +93,13 @@ pub struct CreateVmBase {
     #[arg(long, help = "Virtual Machine Disk Size (GB)", default_value = "70")]
     #[serde(default = "disk_default")]
     disk_size: u32,
+    #[arg(
+        long,
+        help = "Virtual Machine Disk, can be repeated for multiple disks (example: size=70,bus=virtio,format=qcow2,pool=default)",
+        conflicts_with = "disk_size"
+    )]
+    #[serde(default)]
+    disk: Vec<VmDisk>,
     #[arg(
         long,
         help = "Virtual Machine Memory Size (MB)",
@@ -23,22 +110,77 @@ pub struct CreateVmBase {
     vcpu: u32,
     #[arg(
         long,
-        help = "Network (example: bridge=br0)",
+        help = "Network, can be repeated for multiple interfaces (example: bridge=br0)",
         default_value = "network=default,model=virtio"
     )]
-    network: String,
-    #[arg(long, help = "ISO image file path")]
-    iso: String,
+    network: Vec<String>,
+    #[arg(
+        long,
+        help = "ISO image file path",
+        required_unless_present = "location",
+        conflicts_with = "location"
+    )]
+    iso: Option<String>,
+    #[arg(
+        long,
+        help = "Install tree URL used instead of an ISO image (example: http://mirror.example.com/almalinux/8/BaseOS/x86_64/os/)"
+    )]
+    #[serde(default)]
+    location: Option<String>,
+    #[arg(
+        long,
+        help = "Additional file injected into the installer initrd, can be repeated"
+    )]
+    #[serde(default)]
+    initrd_inject: Vec<String>,
+    #[arg(
+        long,
+        help = "Start the VM again when the installation finishes (use with a kickstart that reboots)"
+    )]
+    #[serde(default)]
+    reboot_after_install: bool,
+    #[arg(long, help = "Start the VM when the host boots")]
+    #[serde(default)]
+    autostart: bool,
     #[arg(long, help = "Do not remove temporary directory after finish")]
     do_not_remove_temporary_directory: bool,
     #[arg(long, help = "Do not create VM but print virt-install command")]
     dry_run: bool,
     #[arg(
         long,
-        help = "OS info (example: almalinux8)",
-        default_value = "almalinux8"
+        help = "OS info (example: almalinux8) [default: almalinux8, or the release given by --distro and --os-version for run-all]"
     )]
-    osinfo: String,
+    #[serde(default)]
+    osinfo: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttachedDisk {
+    pub device: String,
+    pub size: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmHardware {
+    pub memory: u32,
+    pub disks: Vec<AttachedDisk>,
+}
+
+impl VmHardware {
+    pub fn disk(&self, device: &str) -> Option<&AttachedDisk> {
+        self.disks.iter().find(|x| x.device == device)
+    }
+}
+
+fn device_name(prefix: &str, index: usize) -> String {
+    let mut suffix = String::new();
+    let mut index = index + 1;
+    while index > 0 {
+        index -= 1;
+        suffix.insert(0, (b'a' + (index % 26) as u8) as char);
+        index /= 26;
+    }
+    format!("{prefix}{suffix}")
 }
 
 fn s(s: impl AsRef<str>) -> String {
@@ -46,8 +188,45 @@ fn s(s: impl AsRef<str>) -> String {
 }
 
 impl CreateVmBase {
+    fn disks(&self) -> Vec<VmDisk> {
+        if self.disk.is_empty() {
+            vec![VmDisk {
+                size: self.disk_size,
+                bus: None,
+                format: None,
+                pool: None,
+            }]
+        } else {
+            self.disk.clone()
+        }
+    }
+
+    fn location_source(&self) -> anyhow::Result<&str> {
+        match (&self.iso, &self.location) {
+            (Some(iso), None) => Ok(iso),
+            (None, Some(location)) => {
+                if !["http://", "https://", "ftp://", "nfs:"]
+                    .iter()
+                    .any(|x| location.starts_with(x))
+                {
+                    return Err(anyhow::anyhow!(
+                        "Location must be an http, https, ftp or nfs URL: {location}"
+                    ));
+                }
+                Ok(location)
+            }
+            _ => Err(anyhow::anyhow!(
+                "Exactly one of ISO and location has to be set"
+            )),
+        }
+    }
+
+    /// Install tree given by `--location`.
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
     pub fn virt_install_cmd(&self, kickstart_path: Option<&str>) -> anyhow::Result<Vec<String>> {
-        let disk = format!("size={}", self.disk_size);
         let vcpu = format!("{}", self.vcpu);
         let memory = format!("memory={0},maxmemory={0}", self.memory);
         let mut cmd = vec![
@@ -55,9 +234,13 @@ impl CreateVmBase {
             s("--name"),
             s(&self.vm_name),
             s("--osinfo"),
-            s(&self.osinfo),
-            s("--disk"),
-            s(disk),
+            s(self.osinfo.as_deref().unwrap_or(DEFAULT_OSINFO)),
+        ];
+        for disk in self.disks() {
+            cmd.push(s("--disk"));
+            cmd.push(disk.virt_install_arg());
+        }
+        cmd.extend([
             s("--vcpu"),
             s(&vcpu),
             s("--cpu"),
@@ -65,13 +248,19 @@ impl CreateVmBase {
             s("--memory"),
             s(&memory),
             s("--location"),
-            s(&self.iso),
-            s("--network"),
-            s(&self.network),
-            s("--noreboot"),
-            s("--autoconsole"),
-            s("text"),
-        ];
+            s(self.location_source()?),
+        ]);
+        for network in &self.network {
+            cmd.push(s("--network"));
+            cmd.push(s(network));
+        }
+        if !self.reboot_after_install {
+            cmd.push(s("--noreboot"));
+        }
+        if self.autostart {
+            cmd.push(s("--autostart"));
+        }
+        cmd.extend([s("--autoconsole"), s("text")]);
 
         if let Some(kickstart_path) = kickstart_path {
             cmd.push(s("--initrd-inject"));
@@ -86,10 +275,87 @@ impl CreateVmBase {
                     .unwrap()
             ));
         }
+        for file in &self.initrd_inject {
+            cmd.push(s("--initrd-inject"));
+            cmd.push(s(file));
+        }
 
         Ok(cmd)
     }
 
+    pub fn hardware(&self) -> VmHardware {
+        let mut virtio = 0;
+        let mut scsi = 0;
+        let mut ide = 0;
+        let disks = self
+            .disks()
+            .iter()
+            .map(|disk| {
+                let device = match disk.bus.as_deref() {
+                    Some("sata") | Some("scsi") | Some("usb") => {
+                        scsi += 1;
+                        device_name("sd", scsi - 1)
+                    }
+                    Some("ide") => {
+                        ide += 1;
+                        device_name("hd", ide - 1)
+                    }
+                    _ => {
+                        virtio += 1;
+                        device_name("vd", virtio - 1)
+                    }
+                };
+                AttachedDisk {
+                    device,
+                    size: disk.size,
+                }
+            })
+            .collect();
+        VmHardware {
+            memory: self.memory,
+            disks,
+        }
+    }
+
+    pub fn add_initrd_inject(&mut self, files: Vec<String>) {
+        for file in files {
+            if !self.initrd_inject.contains(&file) {
+                self.initrd_inject.push(file);
+            }
+        }
+    }
+
+    /// Follows the completion action of the kickstart. A VM that reboots after the
+    /// installation is kept running and also started on host boot.
+    pub fn match_completion_action(&mut self, reboot: bool) {
+        self.reboot_after_install = reboot;
+        if reboot {
+            self.autostart = true;
+        }
+    }
+
+    /// Uses the OS info of the kickstart target unless `--osinfo` is given.
+    pub fn use_osinfo(&mut self, osinfo: String) {
+        if self.osinfo.is_none() {
+            self.osinfo = Some(osinfo);
+        }
+    }
+
+    pub fn match_network_interfaces(&mut self, interfaces: usize) -> anyhow::Result<()> {
+        if self.network.len() == interfaces {
+            return Ok(());
+        }
+        if self.network.len() == 1 {
+            self.network = vec![self.network[0].clone(); interfaces];
+            return Ok(());
+        }
+        Err(anyhow::anyhow!(
+            "{} networks are given but the kickstart has {} network interfaces",
+            self.network.len(),
+            interfaces
+        ))
+    }
+
     pub fn create_vm(&self, kickstart_path: Option<&str>) -> anyhow::Result<()> {
         let cmd = self.virt_install_cmd(kickstart_path)?;
         println!("\"{}\"", cmd.join("\" \""));
@@ -118,3 +384,123 @@ impl CreateVm {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_virt_install_cmd_networks() {
+        let mut base = CreateVmBase {
+            vm_name: "vm1".to_string(),
+            network: vec!["bridge=br0".to_string()],
+            iso: Some("alma.iso".to_string()),
+            ..Default::default()
+        };
+        base.match_network_interfaces(2).unwrap();
+        let cmd = base.virt_install_cmd(None).unwrap();
+        assert_eq!(cmd.iter().filter(|x| x.as_str() == "--network").count(), 2);
+        assert!(cmd.contains(&"--noreboot".to_string()));
+        assert!(!cmd.contains(&"--autostart".to_string()));
+        base.match_completion_action(true);
+        let cmd = base.virt_install_cmd(None).unwrap();
+        assert!(!cmd.contains(&"--noreboot".to_string()));
+        assert!(cmd.contains(&"--autostart".to_string()));
+        assert!(base.match_network_interfaces(3).is_err());
+    }
+
+    #[test]
+    fn test_virt_install_cmd_osinfo() {
+        let mut base = CreateVmBase {
+            vm_name: "vm1".to_string(),
+            network: vec!["bridge=br0".to_string()],
+            iso: Some("alma.iso".to_string()),
+            ..Default::default()
+        };
+        let osinfo = |base: &CreateVmBase| {
+            let cmd = base.virt_install_cmd(None).unwrap();
+            let index = cmd.iter().position(|x| x == "--osinfo").unwrap();
+            cmd[index + 1].clone()
+        };
+        assert_eq!(osinfo(&base), "almalinux8");
+        base.use_osinfo("rocky9".to_string());
+        assert_eq!(osinfo(&base), "rocky9");
+        base.use_osinfo("fedora41".to_string());
+        assert_eq!(osinfo(&base), "rocky9");
+    }
+
+    #[test]
+    fn test_virt_install_cmd_disks() {
+        let base = CreateVmBase {
+            vm_name: "vm1".to_string(),
+            disk: vec![
+                "size=20,bus=virtio,format=qcow2,pool=default"
+                    .parse()
+                    .unwrap(),
+                "size=20".parse().unwrap(),
+                "size=100,bus=sata".parse().unwrap(),
+            ],
+            network: vec!["bridge=br0".to_string()],
+            iso: Some("alma.iso".to_string()),
+            ..Default::default()
+        };
+        let cmd = base.virt_install_cmd(None).unwrap();
+        let disks: Vec<_> = cmd
+            .windows(2)
+            .filter(|x| x[0] == "--disk")
+            .map(|x| x[1].as_str())
+            .collect();
+        assert_eq!(
+            disks,
+            vec![
+                "size=20,bus=virtio,format=qcow2,pool=default",
+                "size=20",
+                "size=100,bus=sata"
+            ]
+        );
+        let devices: Vec<_> = base
+            .hardware()
+            .disks
+            .into_iter()
+            .map(|x| x.device)
+            .collect();
+        assert_eq!(devices, vec!["vda", "vdb", "sda"]);
+        assert_eq!(device_name("vd", 26), "vdaa");
+        assert!("size=10,bus=floppy".parse::<VmDisk>().is_err());
+    }
+
+    #[test]
+    fn test_virt_install_cmd_location() {
+        let base = CreateVmBase {
+            vm_name: "vm1".to_string(),
+            network: vec!["bridge=br0".to_string()],
+            location: Some("http://mirror.example.com/almalinux/8/BaseOS/x86_64/os/".to_string()),
+            ..Default::default()
+        };
+        let mut base = base;
+        base.add_initrd_inject(vec!["tailoring.xml".to_string()]);
+        let cmd = base.virt_install_cmd(Some("/tmp/vm1.ks")).unwrap();
+        let injected: Vec<_> = cmd
+            .windows(2)
+            .filter(|x| x[0] == "--initrd-inject")
+            .map(|x| x[1].as_str())
+            .collect();
+        assert_eq!(injected, vec!["/tmp/vm1.ks", "tailoring.xml"]);
+        let location = cmd.iter().position(|x| x == "--location").unwrap();
+        assert_eq!(
+            cmd[location + 1],
+            "http://mirror.example.com/almalinux/8/BaseOS/x86_64/os/"
+        );
+
+        let invalid = CreateVmBase {
+            location: Some("/srv/almalinux".to_string()),
+            ..base.clone()
+        };
+        assert!(invalid.virt_install_cmd(None).is_err());
+        let invalid = CreateVmBase {
+            iso: Some("alma.iso".to_string()),
+            ..base
+        };
+        assert!(invalid.virt_install_cmd(None).is_err());
+    }
+}
@@ -1,8 +1,21 @@
+use std::collections::HashSet;
 use std::fmt::Display;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
 
+use anyhow::Context;
 use clap::{Args, ValueEnum};
 use serde::{Deserialize, Serialize};
 
+use crate::addon::Addon;
+use crate::createvm::VmHardware;
+use crate::profile::{Distro, ReleaseProfile};
+use crate::scripts::KickstartScripts;
+use crate::spec;
+use crate::sshd::SshdConfig;
+use crate::storage_layout::{recommended_swap_size, StorageLayout, SwapSize};
+use crate::sudo::SudoPolicy;
+
 #[derive(ValueEnum, Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "kebab-case")]
 pub enum NetworkBootproto {
@@ -10,6 +23,321 @@ pub enum NetworkBootproto {
     Static,
 }
 
+#[derive(
+    ValueEnum, Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Ipv6Mode {
+    #[default]
+    Auto,
+    Dhcp,
+    Static,
+    Disabled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NetworkInterface {
+    device: String,
+    bootproto: NetworkBootproto,
+    ip: Option<String>,
+    netmask: Option<String>,
+    gateway: Option<String>,
+    nameservers: Vec<String>,
+    dns_search: Vec<String>,
+    ipv6: Ipv6Mode,
+    ipv6_address: Option<String>,
+    ipv6_gateway: Option<String>,
+    ipv6_nameservers: Vec<String>,
+    bond_slaves: Vec<String>,
+    bond_mode: Option<String>,
+    bond_options: Vec<String>,
+    vlan_id: Option<u16>,
+    bridge_slaves: Vec<String>,
+    activate: bool,
+}
+
+const BOND_MODES: &[&str] = &[
+    "balance-rr",
+    "active-backup",
+    "balance-xor",
+    "broadcast",
+    "802.3ad",
+    "balance-tlb",
+    "balance-alb",
+];
+
+impl FromStr for NetworkInterface {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut interface = NetworkInterface {
+            device: String::new(),
+            bootproto: NetworkBootproto::Dhcp,
+            ip: None,
+            netmask: None,
+            gateway: None,
+            nameservers: vec![],
+            dns_search: vec![],
+            ipv6: Ipv6Mode::Auto,
+            ipv6_address: None,
+            ipv6_gateway: None,
+            ipv6_nameservers: vec![],
+            bond_slaves: vec![],
+            bond_mode: None,
+            bond_options: vec![],
+            vlan_id: None,
+            bridge_slaves: vec![],
+            activate: true,
+        };
+        for (key, value) in spec::parse_spec(s)? {
+            match key.as_str() {
+                "device" => interface.device = spec::required_value(&key, value)?,
+                "bootproto" => {
+                    let value = spec::required_value(&key, value)?;
+                    interface.bootproto = <NetworkBootproto as ValueEnum>::from_str(&value, true)
+                        .map_err(|e| anyhow::anyhow!("Invalid bootproto: {e}"))?;
+                }
+                "ip" => interface.ip = Some(spec::required_value(&key, value)?),
+                "netmask" => interface.netmask = Some(spec::required_value(&key, value)?),
+                "gateway" => interface.gateway = Some(spec::required_value(&key, value)?),
+                "nameserver" => interface
+                    .nameservers
+                    .push(spec::required_value(&key, value)?),
+                "dns-search" => interface
+                    .dns_search
+                    .push(spec::required_value(&key, value)?),
+                "ipv6" => {
+                    let value = spec::required_value(&key, value)?;
+                    interface.ipv6 = <Ipv6Mode as ValueEnum>::from_str(&value, true)
+                        .map_err(|e| anyhow::anyhow!("Invalid ipv6 mode: {e}"))?;
+                }
+                "ipv6-address" => interface.ipv6_address = Some(spec::required_value(&key, value)?),
+                "ipv6-gateway" => interface.ipv6_gateway = Some(spec::required_value(&key, value)?),
+                "ipv6-nameserver" => interface
+                    .ipv6_nameservers
+                    .push(spec::required_value(&key, value)?),
+                "bond-slave" => interface
+                    .bond_slaves
+                    .push(spec::required_value(&key, value)?),
+                "bond-mode" => interface.bond_mode = Some(spec::required_value(&key, value)?),
+                "bond-opt" => interface
+                    .bond_options
+                    .push(spec::required_value(&key, value)?),
+                "vlan-id" => {
+                    let value = spec::required_value(&key, value)?;
+                    interface.vlan_id = Some(
+                        value
+                            .parse()
+                            .map_err(|_| anyhow::anyhow!("Invalid VLAN ID: {value}"))?,
+                    );
+                }
+                "bridge-slave" => interface
+                    .bridge_slaves
+                    .push(spec::required_value(&key, value)?),
+                "activate" => interface.activate = spec::parse_bool(&key, value)?,
+                _ => return Err(anyhow::anyhow!("Unknown network interface option: {key}")),
+            }
+        }
+        if interface.device.is_empty() {
+            return Err(anyhow::anyhow!("Network interface device is not set"));
+        }
+        Ok(interface)
+    }
+}
+
+impl NetworkInterface {
+    fn link_options(&self) -> anyhow::Result<Vec<String>> {
+        let device = &self.device;
+        let mut options = vec![];
+        if let Some(vlan_id) = self.vlan_id {
+            if !(1..=4094).contains(&vlan_id) {
+                return Err(anyhow::anyhow!(
+                    "VLAN ID of {device} must be between 1 and 4094: {vlan_id}"
+                ));
+            }
+            if !self.bridge_slaves.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "{device} cannot be a VLAN and a bridge at the same time"
+                ));
+            }
+            options.push(format!("--vlanid={vlan_id}"));
+        }
+        if !self.bond_slaves.is_empty() && !self.bridge_slaves.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{device} cannot be a bond and a bridge at the same time"
+            ));
+        }
+        if self.bond_slaves.is_empty()
+            && (self.bond_mode.is_some() || !self.bond_options.is_empty())
+        {
+            return Err(anyhow::anyhow!(
+                "Bond mode and options of {device} require bond slaves"
+            ));
+        }
+        if !self.bond_slaves.is_empty() {
+            check_slaves(device, &self.bond_slaves)?;
+            options.push(format!("--bondslaves={}", self.bond_slaves.join(",")));
+            let mut bond_options = vec![];
+            if let Some(mode) = &self.bond_mode {
+                if !BOND_MODES.contains(&mode.as_str()) {
+                    return Err(anyhow::anyhow!(
+                        "Invalid bond mode of {device}: {mode} (expected one of {})",
+                        BOND_MODES.join(", ")
+                    ));
+                }
+                bond_options.push(format!("mode={mode}"));
+            }
+            for option in &self.bond_options {
+                match option.split_once('=') {
+                    Some((key, value))
+                        if !key.is_empty()
+                            && !value.is_empty()
+                            && !option.contains([',', ' ', '"']) => {}
+                    _ => {
+                        return Err(anyhow::anyhow!(
+                            "Invalid bond option of {device}: {option} (expected key=value)"
+                        ))
+                    }
+                }
+                if option.starts_with("mode=") && self.bond_mode.is_some() {
+                    return Err(anyhow::anyhow!(
+                        "Bond mode of {device} is given more than once"
+                    ));
+                }
+                bond_options.push(option.clone());
+            }
+            if !bond_options.is_empty() {
+                options.push(format!("--bondopts={}", bond_options.join(",")));
+            }
+        }
+        if !self.bridge_slaves.is_empty() {
+            check_slaves(device, &self.bridge_slaves)?;
+            options.push(format!("--bridgeslaves={}", self.bridge_slaves.join(",")));
+        }
+        Ok(options)
+    }
+
+    fn slaves(&self) -> &[String] {
+        if self.bond_slaves.is_empty() {
+            &self.bridge_slaves
+        } else {
+            &self.bond_slaves
+        }
+    }
+
+    fn ipv6_options(&self) -> anyhow::Result<Vec<String>> {
+        for nameserver in &self.ipv6_nameservers {
+            nameserver.parse::<Ipv6Addr>().map_err(|_| {
+                anyhow::anyhow!("Invalid IPv6 nameserver for {}: {nameserver}", self.device)
+            })?;
+        }
+        if self.ipv6 != Ipv6Mode::Static
+            && (self.ipv6_address.is_some() || self.ipv6_gateway.is_some())
+        {
+            return Err(anyhow::anyhow!(
+                "IPv6 address and gateway require static IPv6 mode on {}",
+                self.device
+            ));
+        }
+        match self.ipv6 {
+            Ipv6Mode::Auto => Ok(vec!["--ipv6=auto".to_string()]),
+            Ipv6Mode::Dhcp => Ok(vec!["--ipv6=dhcp".to_string()]),
+            Ipv6Mode::Disabled => {
+                if !self.ipv6_nameservers.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "IPv6 nameservers are set but IPv6 is disabled on {}",
+                        self.device
+                    ));
+                }
+                Ok(vec!["--noipv6".to_string()])
+            }
+            Ipv6Mode::Static => {
+                let address = self.ipv6_address.as_ref().ok_or_else(|| {
+                    anyhow::anyhow!("IPv6 address is not set for static device {}", self.device)
+                })?;
+                let (ip, prefix) = address.split_once('/').ok_or_else(|| {
+                    anyhow::anyhow!("IPv6 address must have a prefix length: {address}")
+                })?;
+                ip.parse::<Ipv6Addr>()
+                    .map_err(|_| anyhow::anyhow!("Invalid IPv6 address: {address}"))?;
+                match prefix.parse::<u8>() {
+                    Ok(prefix) if prefix <= 128 => {}
+                    _ => return Err(anyhow::anyhow!("Invalid IPv6 prefix length: {address}")),
+                }
+                let mut options = vec![format!("--ipv6={address}")];
+                if let Some(gateway) = &self.ipv6_gateway {
+                    gateway
+                        .parse::<Ipv6Addr>()
+                        .map_err(|_| anyhow::anyhow!("Invalid IPv6 gateway: {gateway}"))?;
+                    options.push(format!("--ipv6gateway={gateway}"));
+                }
+                Ok(options)
+            }
+        }
+    }
+
+    pub fn generate(&self, hostname: Option<&str>) -> anyhow::Result<String> {
+        let mut options = vec![];
+        let mut nameservers = vec![];
+        match self.bootproto {
+            NetworkBootproto::Dhcp => {
+                options.push("--bootproto=dhcp".to_string());
+                options.push(format!("--device={}", self.device));
+                options.extend(self.link_options()?);
+            }
+            NetworkBootproto::Static => {
+                let ip = self.ip.as_ref().ok_or_else(|| {
+                    anyhow::anyhow!("IP address is not set for static device {}", self.device)
+                })?;
+                let (ip, netmask) = parse_ipv4_address(ip, self.netmask.as_deref())
+                    .with_context(|| format!("Invalid static setup of {}", self.device))?;
+                options.push("--bootproto=static".to_string());
+                options.push(format!("--ip={ip}"));
+                options.push(format!("--netmask={netmask}"));
+                if let Some(gateway) = &self.gateway {
+                    let gateway: Ipv4Addr = gateway.parse().map_err(|_| {
+                        anyhow::anyhow!("Invalid gateway of {}: {gateway}", self.device)
+                    })?;
+                    if !same_subnet(ip, gateway, netmask) || gateway == ip {
+                        return Err(anyhow::anyhow!(
+                            "Gateway {gateway} of {} is not a host in {ip}/{}",
+                            self.device,
+                            u32::from(netmask).count_ones()
+                        ));
+                    }
+                    options.push(format!("--gateway={gateway}"));
+                }
+                options.push(format!("--device={}", self.device));
+                options.extend(self.link_options()?);
+                for nameserver in &self.nameservers {
+                    nameserver.parse::<Ipv4Addr>().map_err(|_| {
+                        anyhow::anyhow!("Invalid nameserver of {}: {nameserver}", self.device)
+                    })?;
+                    nameservers.push(nameserver.clone());
+                }
+            }
+        }
+        nameservers.extend(self.ipv6_nameservers.iter().cloned());
+        if !nameservers.is_empty() {
+            options.push(format!("--nameserver={}", nameservers.join(",")));
+        }
+        if !self.dns_search.is_empty() {
+            for domain in &self.dns_search {
+                check_domain_name(domain)?;
+            }
+            options.push(format!("--ipv4-dns-search={}", self.dns_search.join(",")));
+        }
+        if let Some(hostname) = hostname {
+            options.push(format!("--hostname={hostname}"));
+        }
+        options.extend(self.ipv6_options()?);
+        if self.activate {
+            options.push("--activate".to_string());
+        }
+        Ok(format!("network {}", options.join(" ")))
+    }
+}
+
 #[derive(Args, Debug, Clone, Serialize, Deserialize)]
 pub struct KickstartNetwork {
     #[arg(long, help = "Text mode install")]
@@ -20,7 +348,7 @@ pub struct KickstartNetwork {
     network_bootproto: NetworkBootproto,
     #[arg(
         long,
-        help = "IP Address for manual setup",
+        help = "IP Address for manual setup, CIDR notation is accepted (example: 10.0.0.5/24)",
         default_value = "192.168.100.2"
     )]
     network_ip: String,
@@ -38,35 +366,215 @@ pub struct KickstartNetwork {
     network_gateway: String,
     #[arg(
         long,
-        help = "Nameserver for manual setup",
+        help = "Nameserver for manual setup, can be repeated",
         default_value = "192.168.100.1"
     )]
-    network_nameserver: String,
+    network_nameserver: Vec<String>,
+    #[arg(
+        long,
+        help = "DNS search domain, can be repeated (installer of release 8.7 or later)"
+    )]
+    #[serde(default)]
+    network_dns_search: Vec<String>,
     #[arg(long, help = "Host name", default_value = "localhost.localdomain")]
     network_hostname: String,
+    #[arg(long, help = "IPv6 configuration mode", default_value = "auto")]
+    #[serde(default)]
+    network_ipv6: Ipv6Mode,
+    #[arg(
+        long,
+        help = "IPv6 address with prefix length for static setup (example: 2001:db8::5/64)"
+    )]
+    #[serde(default)]
+    network_ipv6_address: Option<String>,
+    #[arg(long, help = "IPv6 gateway for static setup")]
+    #[serde(default)]
+    network_ipv6_gateway: Option<String>,
+    #[arg(long, help = "IPv6 nameserver, can be repeated")]
+    #[serde(default)]
+    network_ipv6_nameserver: Vec<String>,
+    #[arg(long, help = "Bond slave device, can be repeated")]
+    #[serde(default)]
+    network_bond_slaves: Vec<String>,
+    #[arg(long, help = "Bond mode (example: active-backup)")]
+    #[serde(default)]
+    network_bond_mode: Option<String>,
+    #[arg(
+        long,
+        help = "Additional bond option, can be repeated (example: miimon=100)"
+    )]
+    #[serde(default)]
+    network_bond_opts: Vec<String>,
+    #[arg(long, help = "VLAN ID on top of the network device")]
+    #[serde(default)]
+    network_vlan_id: Option<u16>,
+    #[arg(long, help = "Bridge slave device, can be repeated")]
+    #[serde(default)]
+    network_bridge_slaves: Vec<String>,
+    #[arg(long, help = "Do not activate the network device during installation")]
+    #[serde(default)]
+    network_no_activate: bool,
+    #[arg(
+        long,
+        help = "Additional network interface (example: device=enp2s0,bootproto=static,ip=10.0.1.5,netmask=255.255.255.0,activate=no)"
+    )]
+    #[serde(default)]
+    network_interface: Vec<NetworkInterface>,
+}
+
+fn parse_ipv4_address(ip: &str, netmask: Option<&str>) -> anyhow::Result<(Ipv4Addr, Ipv4Addr)> {
+    if let Some((address, prefix)) = ip.split_once('/') {
+        let address: Ipv4Addr = address
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid IP address: {ip}"))?;
+        let prefix: u32 = match prefix.parse() {
+            Ok(prefix) if prefix <= 32 => prefix,
+            _ => return Err(anyhow::anyhow!("Invalid prefix length: {ip}")),
+        };
+        let netmask = Ipv4Addr::from(u32::MAX.checked_shl(32 - prefix).unwrap_or(0));
+        return Ok((address, netmask));
+    }
+    let address: Ipv4Addr = ip
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid IP address: {ip}"))?;
+    let netmask = netmask.ok_or_else(|| anyhow::anyhow!("Netmask is not set for {ip}"))?;
+    let parsed_netmask: Ipv4Addr = netmask
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid netmask: {netmask}"))?;
+    let bits = u32::from(parsed_netmask);
+    if bits.leading_ones() + bits.trailing_zeros() != 32 {
+        return Err(anyhow::anyhow!("Invalid netmask: {netmask}"));
+    }
+    Ok((address, parsed_netmask))
+}
+
+fn same_subnet(a: Ipv4Addr, b: Ipv4Addr, netmask: Ipv4Addr) -> bool {
+    let mask = u32::from(netmask);
+    u32::from(a) & mask == u32::from(b) & mask
+}
+
+fn check_domain_name(domain: &str) -> anyhow::Result<()> {
+    let valid = !domain.is_empty()
+        && domain.len() <= 253
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Invalid DNS search domain: {domain}"))
+    }
+}
+
+fn check_slaves(device: &str, slaves: &[String]) -> anyhow::Result<()> {
+    let mut seen = HashSet::new();
+    for slave in slaves {
+        if slave == device {
+            return Err(anyhow::anyhow!("{device} cannot be a slave of itself"));
+        }
+        if !seen.insert(slave) {
+            return Err(anyhow::anyhow!("Slave {slave} of {device} is given twice"));
+        }
+    }
+    Ok(())
 }
 
 impl KickstartNetwork {
-    pub fn generate(&self) -> String {
-        match self.network_bootproto {
-            NetworkBootproto::Dhcp => {
-                format!(
-                    "network --bootproto=dhcp --device={} --hostname={} --ipv6=auto --activate",
-                    self.network_device, self.network_hostname
-                )
+    pub fn interfaces(&self) -> Vec<NetworkInterface> {
+        let primary = NetworkInterface {
+            device: self.network_device.clone(),
+            bootproto: self.network_bootproto.clone(),
+            ip: Some(self.network_ip.clone()),
+            netmask: Some(self.network_netmask.clone()),
+            gateway: Some(self.network_gateway.clone()),
+            nameservers: self.network_nameserver.clone(),
+            dns_search: self.network_dns_search.clone(),
+            ipv6: self.network_ipv6.clone(),
+            ipv6_address: self.network_ipv6_address.clone(),
+            ipv6_gateway: self.network_ipv6_gateway.clone(),
+            ipv6_nameservers: self.network_ipv6_nameserver.clone(),
+            bond_slaves: self.network_bond_slaves.clone(),
+            bond_mode: self.network_bond_mode.clone(),
+            bond_options: self.network_bond_opts.clone(),
+            vlan_id: self.network_vlan_id,
+            bridge_slaves: self.network_bridge_slaves.clone(),
+            activate: !self.network_no_activate,
+        };
+        let mut interfaces = vec![primary];
+        interfaces.extend(self.network_interface.iter().cloned());
+        interfaces
+    }
+
+    pub fn physical_devices(&self) -> Vec<String> {
+        let interfaces = self.interfaces();
+        let masters: HashSet<&str> = interfaces
+            .iter()
+            .filter(|x| !x.slaves().is_empty())
+            .map(|x| x.device.as_str())
+            .collect();
+        let mut devices: Vec<String> = vec![];
+        for interface in &interfaces {
+            let candidates = if interface.slaves().is_empty() {
+                std::slice::from_ref(&interface.device)
+            } else {
+                interface.slaves()
+            };
+            for device in candidates {
+                if !masters.contains(device.as_str()) && !devices.contains(device) {
+                    devices.push(device.clone());
+                }
             }
-            NetworkBootproto::Static => {
-                format!(
-                "network --bootproto=static --ip={} --netmask={} --gateway={} --device={} --nameserver={} --hostname={} --ipv6=auto --activate",
-                self.network_ip, self.network_netmask, self.network_gateway, self.network_device, self.network_nameserver, self.network_hostname
-            )
+        }
+        devices
+    }
+
+    pub fn generate(&self) -> anyhow::Result<String> {
+        let interfaces = self.interfaces();
+        let mut devices = HashSet::new();
+        let mut slaves = HashSet::new();
+        let mut lines = vec![];
+        for interface in &interfaces {
+            if !devices.insert((interface.device.as_str(), interface.vlan_id)) {
+                return Err(anyhow::anyhow!(
+                    "Network device {} is defined more than once",
+                    interface.device
+                ));
             }
+            for slave in interface.slaves() {
+                if !slaves.insert(slave.as_str()) {
+                    return Err(anyhow::anyhow!(
+                        "Network device {slave} is a slave of more than one device"
+                    ));
+                }
+            }
+        }
+        for interface in &interfaces {
+            if slaves.contains(interface.device.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "Network device {} is a bond or bridge slave and cannot be configured separately",
+                    interface.device
+                ));
+            }
+        }
+        for (i, interface) in interfaces.iter().enumerate() {
+            let hostname = if i == 0 {
+                Some(self.network_hostname.as_str())
+            } else {
+                None
+            };
+            lines.push(interface.generate(hostname)?);
         }
+        Ok(lines.join("\n"))
     }
 }
 
 #[derive(ValueEnum, Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
+#[allow(clippy::enum_variant_names)]
 pub enum InstallEnvironment {
     MinimalEnvironment,
     GraphicalServerEnvironment,
@@ -95,10 +603,215 @@ impl Display for InstallEnvironment {
     }
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum SelinuxMode {
+    Enforcing,
+    Permissive,
+    Disabled,
+}
+
+impl Display for SelinuxMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SelinuxMode::Enforcing => write!(f, "--enforcing"),
+            SelinuxMode::Permissive => write!(f, "--permissive"),
+            SelinuxMode::Disabled => write!(f, "--disabled"),
+        }
+    }
+}
+
+fn is_package_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.+-*?".contains(c))
+}
+
+fn check_module_stream(module: &str) -> anyhow::Result<()> {
+    let valid = match module.split_once(':') {
+        Some((name, stream)) => {
+            let (stream, profile) = match stream.split_once('/') {
+                Some((stream, profile)) => (stream, Some(profile)),
+                None => (stream, None),
+            };
+            is_package_name(name)
+                && is_package_name(stream)
+                && profile.map(is_package_name).unwrap_or(true)
+        }
+        None => false,
+    };
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid module stream: {module} (expected NAME:STREAM or NAME:STREAM/PROFILE)"
+        ));
+    }
+    Ok(())
+}
+
+fn check_package(package: &str) -> anyhow::Result<()> {
+    let name = package.strip_prefix('-').unwrap_or(package);
+    if name.starts_with("@^") {
+        return Err(anyhow::anyhow!(
+            "Use --environment to select an environment: {package}"
+        ));
+    }
+    if let Some(group) = name.strip_prefix('@') {
+        if group.contains(':') {
+            if package.starts_with('-') {
+                return Err(anyhow::anyhow!(
+                    "Module streams cannot be excluded: {package}"
+                ));
+            }
+            return check_module_stream(group);
+        }
+        if is_package_name(group) {
+            return Ok(());
+        }
+    } else if is_package_name(name) {
+        return Ok(());
+    }
+    Err(anyhow::anyhow!("Invalid package name: {package}"))
+}
+
+const FIREWALL_PROTOCOLS: &[&str] = &["tcp", "udp", "sctp", "dccp"];
+
+fn check_firewall_port(port: &str) -> anyhow::Result<String> {
+    let (number, protocol) = port
+        .split_once(['/', ':'])
+        .ok_or_else(|| anyhow::anyhow!("Firewall port must be PORT/PROTOCOL: {port}"))?;
+    let valid_number = match number.split_once('-') {
+        Some((start, end)) => matches!(
+            (start.parse::<u16>(), end.parse::<u16>()),
+            (Ok(start), Ok(end)) if start > 0 && start < end
+        ),
+        None => matches!(number.parse::<u16>(), Ok(number) if number > 0),
+    };
+    if !valid_number {
+        return Err(anyhow::anyhow!("Invalid firewall port: {port}"));
+    }
+    if !FIREWALL_PROTOCOLS.contains(&protocol) {
+        return Err(anyhow::anyhow!(
+            "Invalid firewall protocol: {port} (expected one of {})",
+            FIREWALL_PROTOCOLS.join(", ")
+        ));
+    }
+    Ok(format!("{number}:{protocol}"))
+}
+
+fn check_service_name(kind: &str, name: &str) -> anyhow::Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.@:-".contains(c))
+    {
+        return Err(anyhow::anyhow!("Invalid {kind} name: {name}"));
+    }
+    Ok(())
+}
+
+const TZDATA: &str = "/usr/share/zoneinfo/tzdata.zi";
+
+/// Zone and link names of a `tzdata.zi` file. Other files in the zoneinfo
+/// directory, such as `zone.tab`, are not timezones.
+fn timezone_names(tzdata: &str) -> HashSet<&str> {
+    tzdata
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("Z") => fields.next(),
+                Some("L") => fields.nth(1),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn check_timezone(timezone: &str) -> anyhow::Result<()> {
+    if timezone.is_empty()
+        || timezone.starts_with('/')
+        || timezone.split('/').any(|x| x == ".." || x == ".")
+    {
+        return Err(anyhow::anyhow!("Invalid timezone: {timezone}"));
+    }
+    // Hosts without the timezone database cannot check the name
+    if let Ok(tzdata) = std::fs::read_to_string(TZDATA) {
+        if !timezone_names(&tzdata).contains(timezone) {
+            return Err(anyhow::anyhow!(
+                "Unknown timezone: {timezone} (not found in {TZDATA})"
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_ntp_host(host: &str) -> anyhow::Result<()> {
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        return Ok(());
+    }
+    check_domain_name(host).map_err(|_| anyhow::anyhow!("Invalid NTP server: {host}"))
+}
+
+#[derive(
+    ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompletionAction {
+    Reboot,
+    Poweroff,
+    #[default]
+    Shutdown,
+    Halt,
+}
+
+impl Display for CompletionAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompletionAction::Reboot => write!(f, "reboot"),
+            CompletionAction::Poweroff => write!(f, "poweroff"),
+            CompletionAction::Shutdown => write!(f, "shutdown"),
+            CompletionAction::Halt => write!(f, "halt"),
+        }
+    }
+}
+
 #[derive(Args, Debug, Clone, Serialize, Deserialize)]
 pub struct KickstartSystem {
+    #[arg(long, help = "Distribution to install", default_value = "almalinux")]
+    #[serde(default)]
+    distro: Distro,
+    #[arg(
+        long,
+        help = "Major version of the installed release, or the release number for Fedora (example: 9)",
+        default_value = "8"
+    )]
+    #[serde(default = "os_version_default")]
+    os_version: u32,
     #[arg(long, help = "Timezone", default_value = "Asia/Tokyo")]
     timezone: String,
+    #[arg(
+        long,
+        help = "NTP server, separated by comma or repeated",
+        value_delimiter = ','
+    )]
+    #[serde(default)]
+    ntp_server: Vec<String>,
+    #[arg(
+        long,
+        help = "NTP pool, separated by comma or repeated (release 9 or later)",
+        value_delimiter = ','
+    )]
+    #[serde(default)]
+    ntp_pool: Vec<String>,
+    #[arg(
+        long,
+        help = "Disable NTP",
+        conflicts_with_all = ["ntp_server", "ntp_pool"]
+    )]
+    #[serde(default)]
+    ntp_disabled: bool,
     #[arg(long, help = "Keyboard layout (Example: \"ja\")", default_value = "us")]
     keyboard: String,
     #[arg(
@@ -109,98 +822,1467 @@ pub struct KickstartSystem {
     language: String,
     #[arg(
         long,
-        help = "Additional packages to install",
-        default_value = "@standard\n@guest-agents"
+        help = "Additional packages to install, separated by comma or repeated",
+        allow_hyphen_values = true,
+        value_delimiter = ',',
+        default_values = ["@standard", "@guest-agents"]
     )]
     packages: Vec<String>,
+    #[arg(
+        long,
+        help = "Packages added to --packages without replacing its default, separated by comma or repeated",
+        allow_hyphen_values = true,
+        value_delimiter = ','
+    )]
+    #[serde(default)]
+    add_packages: Vec<String>,
+    #[arg(
+        long,
+        help = "Package excluded from the installation, separated by comma or repeated (example: cockpit)",
+        allow_hyphen_values = true,
+        value_delimiter = ','
+    )]
+    #[serde(default)]
+    exclude_package: Vec<String>,
+    #[arg(
+        long,
+        help = "Module stream to install, can be repeated (example: postgresql:15 or nodejs:18/minimal)"
+    )]
+    #[serde(default)]
+    module_stream: Vec<String>,
+    #[arg(long, help = "Do not install the @core group")]
+    #[serde(default)]
+    packages_nocore: bool,
+    #[arg(long, help = "Do not install documentation")]
+    #[serde(default)]
+    packages_excludedocs: bool,
+    #[arg(long, help = "Ignore missing packages and groups")]
+    #[serde(default)]
+    packages_ignoremissing: bool,
+    #[arg(long, help = "Do not install weak dependencies")]
+    #[serde(default)]
+    packages_exclude_weakdeps: bool,
+    #[arg(
+        long,
+        help = "Languages installed from packages (example: en_US,ja_JP)"
+    )]
+    #[serde(default)]
+    packages_inst_langs: Option<String>,
     #[arg(
         long,
         help = "Install Environment",
         default_value = "minimal-environment"
     )]
     environment: InstallEnvironment,
-}
-
-#[derive(Args, Debug, Clone, Serialize, Deserialize)]
-pub struct RootPw {
-    #[arg(long, help = "Root plain password", conflicts_with_all = ["rootpw_crypt", "rootpw_keyboard", "rootpw_locked"])]
-    rootpw_plain: Option<String>,
-    #[arg(long, help = "Root crypt password", conflicts_with_all = ["rootpw_plain", "rootpw_keyboard", "rootpw_locked"])]
-    rootpw_crypt: Option<String>,
     #[arg(
         long,
-        help = "Root password from keyboard",
-        conflicts_with_all = ["rootpw_plain", "rootpw_crypt", "rootpw_locked"]
+        help = "Action after installation, run-all boots the VM again only for reboot",
+        default_value = "shutdown"
     )]
-    rootpw_keyboard: bool,
+    #[serde(default)]
+    completion_action: CompletionAction,
+    #[arg(long, help = "SELinux mode")]
+    #[serde(default)]
+    selinux: Option<SelinuxMode>,
     #[arg(
         long,
-        help = "Root user is locked", 
-        conflicts_with_all = ["rootpw_plain", "rootpw_crypt", "rootpw_keyboard"]
+        help = "Disable the firewall",
+        conflicts_with_all = ["firewall_service", "firewall_port"]
     )]
-    rootpw_locked: bool,
-    #[arg(long, help = "Root sshkey")]
-    root_sshkey: Option<String>,
+    #[serde(default)]
+    firewall_disabled: bool,
+    #[arg(
+        long,
+        help = "Service allowed by the firewall, can be repeated (example: https)"
+    )]
+    #[serde(default)]
+    firewall_service: Vec<String>,
+    #[arg(
+        long,
+        help = "Port allowed by the firewall, can be repeated (example: 8080/tcp)"
+    )]
+    #[serde(default)]
+    firewall_port: Vec<String>,
+    #[arg(
+        long,
+        help = "Service enabled on boot, can be repeated (example: cockpit.socket)"
+    )]
+    #[serde(default)]
+    enable_service: Vec<String>,
+    #[arg(long, help = "Service disabled on boot, can be repeated")]
+    #[serde(default)]
+    disable_service: Vec<String>,
 }
 
-impl RootPw {
-    pub fn generate(&self) -> anyhow::Result<String> {
-        let sshkey = if let Some(sshkey) = &self.root_sshkey {
-            format!("\nsshkey --username=root \"{sshkey}\"")
-        } else {
-            "".to_string()
-        };
-        if let Some(pw) = &self.rootpw_plain {
-            Ok(format!("rootpw --plaintext {pw}{sshkey}"))
-        } else if let Some(pw) = &self.rootpw_crypt {
-            Ok(format!("rootpw --iscrypted {pw}{sshkey}"))
-        } else if self.rootpw_keyboard {
-            let encrypt_password = crate::passwd::read_and_encrypt_password("Root Password: ")?;
-            Ok(format!("rootpw --iscrypted {encrypt_password}{sshkey}"))
-        } else if self.rootpw_locked {
-            Ok("rootpw --lock".to_string())
-        } else {
-            Err(anyhow::anyhow!("Root password is not set"))
-        }
-    }
+fn os_version_default() -> u32 {
+    8
 }
 
-#[derive(Args, Debug, Clone, Serialize, Deserialize)]
-pub struct Storage {
-    #[arg(long, help = "Storage device", default_value = "vda")]
-    storage_device: String,
-    #[arg(long, help = "Filesystem", default_value = "xfs")]
-    filesystem: String,
-}
+impl KickstartSystem {
+    pub fn profile(&self) -> anyhow::Result<ReleaseProfile> {
+        ReleaseProfile::new(self.distro, self.os_version)
+    }
 
-impl Storage {
-    pub fn generate(&self) -> String {
-        let storage_device = &self.storage_device;
-        let filesystem = &self.filesystem;
-        format!(
-            r#"ignoredisk --only-use={storage_device}
-# Partition clearing information
-clearpart --none --initlabel
-# Disk partitioning information
-reqpart
-part pv.116 --fstype="lvmpv" --ondisk={storage_device} --size=15360 --grow
-part /boot --fstype="{filesystem}" --ondisk={storage_device} --size=1024
-volgroup almalinux --pesize=4096 pv.116
-logvol swap --fstype="swap" --size=4030 --name=swap --vgname=almalinux
-logvol / --fstype="{filesystem}" --size=10240 --name=root --vgname=almalinux --grow
-"#
-        )
+    /// Returns the `timezone` line and the `timesource` lines, which are only used
+    /// from release 9.
+    pub fn time(&self) -> anyhow::Result<String> {
+        check_timezone(&self.timezone)?;
+        for host in self.ntp_server.iter().chain(self.ntp_pool.iter()) {
+            check_ntp_host(host)?;
+        }
+        let mut lines = vec![format!("timezone {} --utc", self.timezone)];
+        if self.profile()?.has_timesource() {
+            if self.ntp_disabled {
+                lines.push("timesource --ntp-disable".to_string());
+            }
+            for server in &self.ntp_server {
+                lines.push(format!("timesource --ntp-server={server}"));
+            }
+            for pool in &self.ntp_pool {
+                lines.push(format!("timesource --ntp-pool={pool}"));
+            }
+        } else {
+            if !self.ntp_pool.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "NTP pools require release 9 or later, use --ntp-server instead"
+                ));
+            }
+            if self.ntp_disabled {
+                lines[0].push_str(" --nontp");
+            } else if !self.ntp_server.is_empty() {
+                lines[0].push_str(&format!(" --ntpservers={}", self.ntp_server.join(",")));
+            }
+        }
+        Ok(lines.join("\n"))
     }
-}
 
-#[derive(Args, Debug, Clone, Serialize, Deserialize)]
-pub struct UserPw {
-    #[arg(long, help = "Username")]
-    username: Option<String>,
-    #[arg(long, help = "User plain password", conflicts_with_all = ["user_crypt", "user_keyboard"])]
-    user_plain: Option<String>,
-    #[arg(long, help = "User crypt password", conflicts_with_all = ["user_plain", "user_keyboard"])]
-    user_crypt: Option<String>,
+    /// Returns the `%packages` options and the package lines. `required` lists
+    /// packages needed by other options.
+    pub fn packages(&self, required: &[&str]) -> anyhow::Result<(String, String)> {
+        let mut options = String::new();
+        if self.packages_nocore {
+            options.push_str(" --nocore");
+        }
+        if self.packages_excludedocs {
+            options.push_str(" --excludedocs");
+        }
+        if self.packages_ignoremissing {
+            options.push_str(" --ignoremissing");
+        }
+        if self.packages_exclude_weakdeps {
+            options.push_str(" --exclude-weakdeps");
+        }
+        if let Some(langs) = &self.packages_inst_langs {
+            if langs.is_empty()
+                || !langs
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_@.,-".contains(c))
+            {
+                return Err(anyhow::anyhow!("Invalid package languages: {langs}"));
+            }
+            options.push_str(&format!(" {}={langs}", self.profile()?.inst_langs_option()));
+        }
+
+        for module in &self.module_stream {
+            check_module_stream(module)?;
+        }
+        let mut packages: Vec<String> = Vec::new();
+        let included = self
+            .packages
+            .iter()
+            .chain(self.add_packages.iter())
+            .cloned()
+            .chain(self.module_stream.iter().map(|x| format!("@{x}")))
+            .chain(required.iter().map(|x| x.to_string()));
+        for package in included {
+            check_package(&package)?;
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+        for package in &self.exclude_package {
+            if package.starts_with('-') {
+                return Err(anyhow::anyhow!(
+                    "Give excluded packages without the leading -: {package}"
+                ));
+            }
+            check_package(package)?;
+            if packages.contains(package) {
+                return Err(anyhow::anyhow!(
+                    "Package {package} is both included and excluded"
+                ));
+            }
+            packages.push(format!("-{package}"));
+        }
+        Ok((options, packages.join("\n")))
+    }
+
+    pub fn generate(&self) -> anyhow::Result<String> {
+        let mut lines = Vec::new();
+        if let Some(selinux) = self.selinux {
+            lines.push(format!("selinux {selinux}"));
+        }
+
+        if self.firewall_disabled {
+            if !self.firewall_service.is_empty() || !self.firewall_port.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Firewall services and ports are given but the firewall is disabled"
+                ));
+            }
+            lines.push("firewall --disabled".to_string());
+        } else if !self.firewall_service.is_empty() || !self.firewall_port.is_empty() {
+            let mut firewall = "firewall --enabled".to_string();
+            if !self.firewall_service.is_empty() {
+                for service in &self.firewall_service {
+                    check_service_name("firewall service", service)?;
+                }
+                firewall.push_str(&format!(" --service={}", self.firewall_service.join(",")));
+            }
+            if !self.firewall_port.is_empty() {
+                let ports = self
+                    .firewall_port
+                    .iter()
+                    .map(|x| check_firewall_port(x))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                firewall.push_str(&format!(" --port={}", ports.join(",")));
+            }
+            lines.push(firewall);
+        }
+
+        if !self.enable_service.is_empty() || !self.disable_service.is_empty() {
+            let mut services = "services".to_string();
+            for service in self
+                .enable_service
+                .iter()
+                .chain(self.disable_service.iter())
+            {
+                check_service_name("service", service)?;
+            }
+            if let Some(service) = self
+                .enable_service
+                .iter()
+                .find(|x| self.disable_service.contains(x))
+            {
+                return Err(anyhow::anyhow!(
+                    "Service {service} is both enabled and disabled"
+                ));
+            }
+            if !self.disable_service.is_empty() {
+                services.push_str(&format!(" --disabled={}", self.disable_service.join(",")));
+            }
+            if !self.enable_service.is_empty() {
+                services.push_str(&format!(" --enabled={}", self.enable_service.join(",")));
+            }
+            lines.push(services);
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+const CDROM_APPSTREAM: &str = "file:///run/install/sources/mount-0000-cdrom/AppStream";
+
+fn check_url(kind: &str, url: &str, schemes: &[&str]) -> anyhow::Result<()> {
+    if !schemes.iter().any(|x| url.starts_with(&format!("{x}:")))
+        || url.contains(char::is_whitespace)
+    {
+        return Err(anyhow::anyhow!(
+            "Invalid {kind} URL: {url} (expected one of {} scheme)",
+            schemes.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct Repo {
+    name: String,
+    baseurl: Option<String>,
+    mirrorlist: Option<String>,
+    cost: Option<u32>,
+    proxy: Option<String>,
+    install: bool,
+}
+
+impl FromStr for Repo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut repo = Repo::default();
+        for (key, value) in spec::parse_spec(s)? {
+            match key.as_str() {
+                "name" => repo.name = spec::required_value(&key, value)?,
+                "baseurl" => repo.baseurl = Some(spec::required_value(&key, value)?),
+                "mirrorlist" => repo.mirrorlist = Some(spec::required_value(&key, value)?),
+                "cost" => {
+                    let value = spec::required_value(&key, value)?;
+                    repo.cost = Some(
+                        value
+                            .parse()
+                            .map_err(|_| anyhow::anyhow!("Invalid repository cost: {value}"))?,
+                    );
+                }
+                "proxy" => repo.proxy = Some(spec::required_value(&key, value)?),
+                "install" => repo.install = spec::parse_bool(&key, value)?,
+                _ => return Err(anyhow::anyhow!("Unknown repository option: {key}")),
+            }
+        }
+        if repo.name.is_empty()
+            || !repo
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c))
+        {
+            return Err(anyhow::anyhow!("Invalid repository name: {}", repo.name));
+        }
+        Ok(repo)
+    }
+}
+
+impl Repo {
+    fn generate(&self) -> anyhow::Result<String> {
+        let mut line = format!("repo --name=\"{}\"", self.name);
+        match (&self.baseurl, &self.mirrorlist) {
+            (Some(baseurl), None) => {
+                check_url(
+                    "repository",
+                    baseurl,
+                    &["http", "https", "ftp", "nfs", "file"],
+                )?;
+                line.push_str(&format!(" --baseurl={baseurl}"));
+            }
+            (None, Some(mirrorlist)) => {
+                check_url("mirrorlist", mirrorlist, &["http", "https", "ftp"])?;
+                line.push_str(&format!(" --mirrorlist={mirrorlist}"));
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Exactly one of baseurl and mirrorlist has to be set for repository {}",
+                    self.name
+                ))
+            }
+        }
+        if let Some(cost) = self.cost {
+            line.push_str(&format!(" --cost={cost}"));
+        }
+        if let Some(proxy) = &self.proxy {
+            check_url("proxy", proxy, &["http", "https"])?;
+            line.push_str(&format!(" --proxy={proxy}"));
+        }
+        if self.install {
+            line.push_str(" --install");
+        }
+        Ok(line)
+    }
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize, Default)]
+pub struct InstallSource {
+    #[arg(
+        long,
+        help = "Install from a network install tree instead of the CDROM (example: http://mirror.example.com/almalinux/8/BaseOS/x86_64/os/)",
+        conflicts_with = "install_nfs"
+    )]
+    #[serde(default)]
+    install_url: Option<String>,
+    #[arg(
+        long,
+        help = "Install from an NFS install tree instead of the CDROM (example: nfs.example.com:/srv/almalinux/8)"
+    )]
+    #[serde(default)]
+    install_nfs: Option<String>,
+    #[arg(long, help = "Proxy for the install URL", requires = "install_url")]
+    #[serde(default)]
+    install_proxy: Option<String>,
+    #[arg(
+        long,
+        help = "Additional repository, can be repeated (example: name=epel,mirrorlist=https://mirrors.fedoraproject.org/mirrorlist?repo=epel-8&arch=x86_64,cost=50)"
+    )]
+    #[serde(default)]
+    repo: Vec<Repo>,
+}
+
+impl InstallSource {
+    /// Installs from a virt-install `--location`, which is an install tree URL or
+    /// `nfs:SERVER:/DIRECTORY`, unless another install source is given.
+    pub fn use_install_location(&mut self, location: &str) {
+        if self.install_url.is_some() || self.install_nfs.is_some() {
+            return;
+        }
+        if let Some(nfs) = location.strip_prefix("nfs:") {
+            self.install_nfs = Some(nfs.to_string());
+        } else {
+            self.install_url = Some(location.to_string());
+        }
+    }
+
+    fn appstream_url(&self) -> anyhow::Result<String> {
+        if let Some(url) = &self.install_url {
+            if url.contains("/BaseOS/") {
+                return Ok(url.replace("/BaseOS/", "/AppStream/"));
+            }
+            return Ok(format!("{}/AppStream", url.trim_end_matches('/')));
+        }
+        if let Some(nfs) = &self.install_nfs {
+            let (server, dir) = Self::nfs_server_dir(nfs)?;
+            return Ok(format!(
+                "nfs://{server}:{}/AppStream",
+                dir.trim_end_matches('/')
+            ));
+        }
+        Ok(CDROM_APPSTREAM.to_string())
+    }
+
+    fn nfs_server_dir(nfs: &str) -> anyhow::Result<(&str, &str)> {
+        match nfs.split_once(':') {
+            Some((server, dir)) if !server.is_empty() && dir.starts_with('/') => Ok((server, dir)),
+            _ => Err(anyhow::anyhow!(
+                "NFS install source must be SERVER:/DIRECTORY: {nfs}"
+            )),
+        }
+    }
+
+    /// Returns the `repo` lines and the install source command.
+    pub fn generate(&self, profile: &ReleaseProfile) -> anyhow::Result<(String, String)> {
+        let mut repos = Vec::new();
+        let mut names = HashSet::new();
+        if profile.has_appstream() {
+            repos.push(format!(
+                "repo --name=\"AppStream\" --baseurl={}",
+                self.appstream_url()?
+            ));
+            names.insert("AppStream".to_string());
+        }
+        for repo in &self.repo {
+            if !names.insert(repo.name.clone()) {
+                return Err(anyhow::anyhow!("Duplicated repository: {}", repo.name));
+            }
+            repos.push(repo.generate()?);
+        }
+
+        let source = if let Some(url) = &self.install_url {
+            check_url("install", url, &["http", "https", "ftp", "file"])?;
+            if let Some(proxy) = &self.install_proxy {
+                check_url("proxy", proxy, &["http", "https"])?;
+                format!("url --url=\"{url}\" --proxy=\"{proxy}\"")
+            } else {
+                format!("url --url=\"{url}\"")
+            }
+        } else if let Some(nfs) = &self.install_nfs {
+            let (server, dir) = Self::nfs_server_dir(nfs)?;
+            format!("nfs --server={server} --dir={dir}")
+        } else {
+            "cdrom".to_string()
+        };
+        Ok((repos.join("\n"), source))
+    }
+}
+
+#[derive(
+    ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum OscapContentType {
+    #[default]
+    ScapSecurityGuide,
+    Datastream,
+    Archive,
+    Rpm,
+}
+
+impl Display for OscapContentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OscapContentType::ScapSecurityGuide => write!(f, "scap-security-guide"),
+            OscapContentType::Datastream => write!(f, "datastream"),
+            OscapContentType::Archive => write!(f, "archive"),
+            OscapContentType::Rpm => write!(f, "rpm"),
+        }
+    }
+}
+
+fn check_scap_id(kind: &str, id: &str) -> anyhow::Result<()> {
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.:-".contains(c))
+    {
+        return Err(anyhow::anyhow!("Invalid OpenSCAP {kind}: {id}"));
+    }
+    Ok(())
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize, Default)]
+pub struct KickstartAddons {
+    #[arg(long, help = "Disable kdump", conflicts_with = "kdump_reserve_mb")]
+    #[serde(default)]
+    kdump_disabled: bool,
+    #[arg(long, help = "Memory reserved for kdump in MiB (default: auto)")]
+    #[serde(default)]
+    kdump_reserve_mb: Option<u32>,
+    #[arg(
+        long,
+        help = "Additional %addon section, can be repeated (example: name=com_example_addon,option=--enable,line=\"key = value\")"
+    )]
+    #[serde(default)]
+    addon: Vec<Addon>,
+    #[arg(
+        long,
+        help = "OpenSCAP profile applied during installation (example: xccdf_org.ssgproject.content_profile_cis)"
+    )]
+    #[serde(default)]
+    oscap_profile: Option<String>,
+    #[arg(
+        long,
+        help = "OpenSCAP content type (scap-security-guide uses the content shipped with the installer)",
+        default_value = "scap-security-guide"
+    )]
+    #[serde(default)]
+    oscap_content_type: OscapContentType,
+    #[arg(
+        long,
+        help = "OpenSCAP content URL for datastream, archive and rpm content"
+    )]
+    #[serde(default)]
+    oscap_content_url: Option<String>,
+    #[arg(long, help = "OpenSCAP datastream ID")]
+    #[serde(default)]
+    oscap_datastream_id: Option<String>,
+    #[arg(long, help = "OpenSCAP XCCDF benchmark ID")]
+    #[serde(default)]
+    oscap_xccdf_id: Option<String>,
+    #[arg(
+        long,
+        help = "OpenSCAP tailoring file, injected into the installer initrd by run-all"
+    )]
+    #[serde(default)]
+    oscap_tailoring_file: Option<String>,
+}
+
+impl KickstartAddons {
+    pub fn generate(
+        &self,
+        hardware: Option<&VmHardware>,
+        profile: &ReleaseProfile,
+    ) -> anyhow::Result<Vec<Addon>> {
+        let mut addons = vec![self.kdump(hardware, profile)?];
+        addons.extend(self.oscap(profile)?);
+        addons.extend(self.addon.iter().cloned());
+        let mut names = HashSet::new();
+        for addon in &addons {
+            if !names.insert(addon.name()) {
+                return Err(anyhow::anyhow!("Duplicated addon: {}", addon.name()));
+            }
+        }
+        Ok(addons)
+    }
+
+    /// Host files the installer needs in its initrd.
+    pub fn initrd_files(&self) -> Vec<String> {
+        self.oscap_tailoring_file.iter().cloned().collect()
+    }
+
+    fn oscap(&self, release: &ReleaseProfile) -> anyhow::Result<Option<Addon>> {
+        let Some(profile) = &self.oscap_profile else {
+            if self.oscap_content_url.is_some()
+                || self.oscap_datastream_id.is_some()
+                || self.oscap_xccdf_id.is_some()
+                || self.oscap_tailoring_file.is_some()
+            {
+                return Err(anyhow::anyhow!(
+                    "OpenSCAP options are given without --oscap-profile"
+                ));
+            }
+            return Ok(None);
+        };
+        check_scap_id("profile", profile)?;
+
+        let mut addon = Addon::new(release.oscap_addon())
+            .line(format!("    content-type = {}", self.oscap_content_type));
+        match (self.oscap_content_type, &self.oscap_content_url) {
+            (OscapContentType::ScapSecurityGuide, None) => {}
+            (OscapContentType::ScapSecurityGuide, Some(_)) => {
+                return Err(anyhow::anyhow!(
+                    "OpenSCAP content URL cannot be used with scap-security-guide content"
+                ))
+            }
+            (_, Some(url)) => {
+                check_url("OpenSCAP content", url, &["http", "https", "ftp", "file"])?;
+                addon = addon.line(format!("    content-url = {url}"));
+            }
+            (content_type, None) => {
+                return Err(anyhow::anyhow!(
+                    "OpenSCAP content URL is required for {content_type} content"
+                ))
+            }
+        }
+        if let Some(datastream_id) = &self.oscap_datastream_id {
+            check_scap_id("datastream ID", datastream_id)?;
+            addon = addon.line(format!("    datastream-id = {datastream_id}"));
+        }
+        if let Some(xccdf_id) = &self.oscap_xccdf_id {
+            check_scap_id("XCCDF ID", xccdf_id)?;
+            addon = addon.line(format!("    xccdf-id = {xccdf_id}"));
+        }
+        if let Some(tailoring_file) = &self.oscap_tailoring_file {
+            let file_name = std::path::Path::new(tailoring_file)
+                .file_name()
+                .and_then(|x| x.to_str())
+                .ok_or_else(|| anyhow::anyhow!("Invalid tailoring file: {tailoring_file}"))?;
+            if !std::path::Path::new(tailoring_file).is_file() {
+                return Err(anyhow::anyhow!(
+                    "Tailoring file not found: {tailoring_file}"
+                ));
+            }
+            // --initrd-inject places the file at the root of the installer
+            addon = addon.line(format!("    tailoring-path = /{file_name}"));
+        }
+        Ok(Some(addon.line(format!("    profile = {profile}"))))
+    }
+
+    fn kdump(
+        &self,
+        hardware: Option<&VmHardware>,
+        profile: &ReleaseProfile,
+    ) -> anyhow::Result<Addon> {
+        let addon = Addon::new("com_redhat_kdump");
+        if self.kdump_disabled {
+            return Ok(addon.option("--disable"));
+        }
+        if self.kdump_reserve_mb.is_none() && profile.kdump_auto_deprecated() {
+            return Ok(addon.option("--enable"));
+        }
+        let reserve_mb = match self.kdump_reserve_mb {
+            Some(0) => return Err(anyhow::anyhow!("kdump reservation must be larger than 0")),
+            Some(reserve_mb) => {
+                if let Some(hardware) = hardware {
+                    if reserve_mb > hardware.memory / 2 {
+                        return Err(anyhow::anyhow!(
+                            "kdump reservation of {reserve_mb} MiB is too large for {} MiB memory",
+                            hardware.memory
+                        ));
+                    }
+                }
+                reserve_mb.to_string()
+            }
+            None => "'auto'".to_string(),
+        };
+        Ok(addon
+            .option("--enable")
+            .option(format!("--reserve-mb={reserve_mb}")))
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum BootloaderLocation {
+    Mbr,
+    Partition,
+    Boot,
+    None,
+}
+
+impl Display for BootloaderLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BootloaderLocation::Mbr => write!(f, "mbr"),
+            BootloaderLocation::Partition => write!(f, "partition"),
+            BootloaderLocation::Boot => write!(f, "boot"),
+            BootloaderLocation::None => write!(f, "none"),
+        }
+    }
+}
+
+const SERIAL_CONSOLE_ARGS: &[&str] = &["console=tty0", "console=ttyS0,115200"];
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Bootloader {
+    #[arg(
+        long,
+        help = "Kernel argument of the installed system, can be repeated (example: crashkernel=256M)"
+    )]
+    #[serde(default)]
+    bootloader_append: Vec<String>,
+    #[arg(
+        long,
+        help = "Do not add console=ttyS0,115200 to the kernel arguments of the installed system"
+    )]
+    #[serde(default)]
+    no_serial_console: bool,
+    #[arg(
+        long,
+        help = "GRUB plain password",
+        conflicts_with = "bootloader_password_crypt"
+    )]
+    #[serde(default)]
+    bootloader_password_plain: Option<String>,
+    #[arg(long, help = "GRUB password hash created by grub2-mkpasswd-pbkdf2")]
+    #[serde(default)]
+    bootloader_password_crypt: Option<String>,
+    #[arg(long, help = "GRUB menu timeout in seconds")]
+    #[serde(default)]
+    bootloader_timeout: Option<u32>,
+    #[arg(long, help = "Bootloader location")]
+    #[serde(default)]
+    bootloader_location: Option<BootloaderLocation>,
+}
+
+impl Bootloader {
+    pub fn generate(&self) -> anyhow::Result<String> {
+        let mut append: Vec<&str> = Vec::new();
+        if !self.no_serial_console {
+            append.extend(SERIAL_CONSOLE_ARGS);
+        }
+        for arg in &self.bootloader_append {
+            if arg.is_empty()
+                || arg
+                    .chars()
+                    .any(|c| c.is_whitespace() || c == '"' || c == '\\')
+            {
+                return Err(anyhow::anyhow!(
+                    "Invalid kernel argument: {arg:?} (give one argument per --bootloader-append)"
+                ));
+            }
+            if !append.contains(&arg.as_str()) {
+                append.push(arg);
+            }
+        }
+
+        let mut line = "bootloader".to_string();
+        if !append.is_empty() {
+            line.push_str(&format!(" --append=\"{}\"", append.join(" ")));
+        }
+        if let Some(location) = self.bootloader_location {
+            line.push_str(&format!(" --location={location}"));
+        }
+        if let Some(timeout) = self.bootloader_timeout {
+            line.push_str(&format!(" --timeout={timeout}"));
+        }
+        if let Some(password) = &self.bootloader_password_plain {
+            if password.is_empty() {
+                return Err(anyhow::anyhow!("GRUB password is empty"));
+            }
+            line.push_str(&format!(" --password={}", quote(password)));
+        } else if let Some(password) = &self.bootloader_password_crypt {
+            if !password.starts_with("grub.pbkdf2.") || password.contains(char::is_whitespace) {
+                return Err(anyhow::anyhow!(
+                    "GRUB password hash must be created by grub2-mkpasswd-pbkdf2"
+                ));
+            }
+            line.push_str(&format!(" --iscrypted --password={password}"));
+        }
+        Ok(line)
+    }
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct RootPw {
+    #[arg(long, help = "Root plain password", conflicts_with_all = ["rootpw_crypt", "rootpw_keyboard", "rootpw_locked"])]
+    rootpw_plain: Option<String>,
+    #[arg(long, help = "Root crypt password", conflicts_with_all = ["rootpw_plain", "rootpw_keyboard", "rootpw_locked"])]
+    rootpw_crypt: Option<String>,
+    #[arg(
+        long,
+        help = "Root password from keyboard",
+        conflicts_with_all = ["rootpw_plain", "rootpw_crypt", "rootpw_locked"]
+    )]
+    rootpw_keyboard: bool,
+    #[arg(
+        long,
+        help = "Root user is locked", 
+        conflicts_with_all = ["rootpw_plain", "rootpw_crypt", "rootpw_keyboard"]
+    )]
+    rootpw_locked: bool,
+    #[arg(long, help = "Root sshkey")]
+    root_sshkey: Option<String>,
+}
+
+impl RootPw {
+    /// `allow_ssh` keeps password SSH login for root on releases that disable it.
+    pub fn generate(&self, allow_ssh: bool) -> anyhow::Result<String> {
+        let allow_ssh = if allow_ssh { " --allow-ssh" } else { "" };
+        let sshkey = if let Some(sshkey) = &self.root_sshkey {
+            format!("\nsshkey --username=root \"{sshkey}\"")
+        } else {
+            "".to_string()
+        };
+        if let Some(pw) = &self.rootpw_plain {
+            Ok(format!("rootpw --plaintext {pw}{allow_ssh}{sshkey}"))
+        } else if let Some(pw) = &self.rootpw_crypt {
+            Ok(format!("rootpw --iscrypted {pw}{allow_ssh}{sshkey}"))
+        } else if self.rootpw_keyboard {
+            let encrypt_password = crate::passwd::read_and_encrypt_password("Root Password: ")?;
+            Ok(format!(
+                "rootpw --iscrypted {encrypt_password}{allow_ssh}{sshkey}"
+            ))
+        } else if self.rootpw_locked {
+            Ok("rootpw --lock".to_string())
+        } else {
+            Err(anyhow::anyhow!("Root password is not set"))
+        }
+    }
+}
+
+#[derive(
+    ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum StorageScheme {
+    #[default]
+    Lvm,
+    ThinLvm,
+    Plain,
+    Btrfs,
+    Raid1,
+    AutopartLvm,
+    AutopartPlain,
+    AutopartThinp,
+    AutopartBtrfs,
+}
+
+impl StorageScheme {
+    fn autopart_type(&self) -> Option<&'static str> {
+        match self {
+            StorageScheme::AutopartLvm => Some("lvm"),
+            StorageScheme::AutopartPlain => Some("plain"),
+            StorageScheme::AutopartThinp => Some("thinp"),
+            StorageScheme::AutopartBtrfs => Some("btrfs"),
+            _ => None,
+        }
+    }
+
+    fn is_btrfs(&self) -> bool {
+        matches!(self, StorageScheme::Btrfs | StorageScheme::AutopartBtrfs)
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum EncryptionTarget {
+    Pv,
+    Lv,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum LuksVersion {
+    Luks1,
+    Luks2,
+}
+
+impl Display for LuksVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LuksVersion::Luks1 => write!(f, "luks1"),
+            LuksVersion::Luks2 => write!(f, "luks2"),
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LuksEncryption {
+    #[arg(long, help = "LUKS version")]
+    #[serde(default)]
+    luks_version: Option<LuksVersion>,
+    #[arg(long, help = "LUKS plain passphrase", conflicts_with_all = ["luks_passphrase_keyboard", "luks_passphrase_file"])]
+    #[serde(default)]
+    luks_passphrase_plain: Option<String>,
+    #[arg(
+        long,
+        help = "LUKS passphrase from keyboard",
+        conflicts_with_all = ["luks_passphrase_plain", "luks_passphrase_file"]
+    )]
+    #[serde(default)]
+    luks_passphrase_keyboard: bool,
+    #[arg(
+        long,
+        help = "LUKS passphrase file path",
+        conflicts_with_all = ["luks_passphrase_plain", "luks_passphrase_keyboard"]
+    )]
+    #[serde(default)]
+    luks_passphrase_file: Option<String>,
+    #[arg(
+        long,
+        help = "URL of an X.509 certificate to escrow LUKS data encryption keys"
+    )]
+    #[serde(default)]
+    luks_escrowcert: Option<String>,
+    #[arg(
+        long,
+        help = "Add a random backup passphrase escrowed with the certificate",
+        requires = "luks_escrowcert"
+    )]
+    #[serde(default)]
+    luks_backuppassphrase: bool,
+}
+
+impl LuksEncryption {
+    fn passphrase(&self) -> anyhow::Result<String> {
+        let passphrase = if let Some(passphrase) = &self.luks_passphrase_plain {
+            passphrase.clone()
+        } else if self.luks_passphrase_keyboard {
+            crate::passwd::read_and_confirm_passwd("LUKS Passphrase: ")?
+        } else if let Some(path) = &self.luks_passphrase_file {
+            std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read LUKS passphrase file {path}"))?
+                .trim_end_matches(['\r', '\n'])
+                .to_string()
+        } else {
+            return Err(anyhow::anyhow!("LUKS passphrase is not set"));
+        };
+        if passphrase.is_empty() || passphrase.contains(['\r', '\n']) {
+            return Err(anyhow::anyhow!(
+                "LUKS passphrase must be a single non-empty line"
+            ));
+        }
+        Ok(passphrase)
+    }
+
+    pub fn generate(&self) -> anyhow::Result<String> {
+        if self.luks_backuppassphrase && self.luks_escrowcert.is_none() {
+            return Err(anyhow::anyhow!(
+                "LUKS backup passphrase requires an escrow certificate"
+            ));
+        }
+        let mut options = format!("--encrypted --passphrase={}", quote(&self.passphrase()?));
+        if let Some(version) = &self.luks_version {
+            options.push_str(&format!(" --luks-version={version}"));
+        }
+        if let Some(escrowcert) = &self.luks_escrowcert {
+            options.push_str(&format!(" --escrowcert={escrowcert}"));
+        }
+        if self.luks_backuppassphrase {
+            options.push_str(" --backuppassphrase");
+        }
+        Ok(options)
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum SwapPolicy {
+    None,
+    Recommended,
+    Fixed,
+}
+
+#[derive(
+    ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClearpartPolicy {
+    #[default]
+    None,
+    All,
+    Linux,
+}
+
+impl Display for ClearpartPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClearpartPolicy::None => write!(f, "--none"),
+            ClearpartPolicy::All => write!(f, "--all"),
+            ClearpartPolicy::Linux => write!(f, "--linux"),
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DiskInitialization {
+    #[arg(
+        long,
+        help = "Partitions removed before partitioning",
+        default_value = "none"
+    )]
+    #[serde(default)]
+    clearpart: ClearpartPolicy,
+    #[arg(
+        long,
+        help = "Limit --clearpart to a drive, can be repeated [default: all storage devices]"
+    )]
+    #[serde(default)]
+    clearpart_drive: Vec<String>,
+    #[arg(long, help = "Initialize disks with invalid partition tables")]
+    #[serde(default)]
+    zerombr: bool,
+    #[arg(
+        long,
+        help = "Allow --clearpart all without --clearpart-drive, and --zerombr with --clearpart all or linux"
+    )]
+    #[serde(default)]
+    confirm_disk_initialization: bool,
+}
+
+impl DiskInitialization {
+    pub fn generate(&self, disks: &[String]) -> anyhow::Result<String> {
+        if self.clearpart == ClearpartPolicy::None && !self.clearpart_drive.is_empty() {
+            return Err(anyhow::anyhow!(
+                "--clearpart-drive requires --clearpart all or linux"
+            ));
+        }
+        if let Some(drive) = self.clearpart_drive.iter().find(|x| !disks.contains(x)) {
+            return Err(anyhow::anyhow!(
+                "Clearpart drive {drive} is not a storage device (storage devices: {})",
+                disks.join(", ")
+            ));
+        }
+        if !self.confirm_disk_initialization {
+            if self.clearpart == ClearpartPolicy::All && self.clearpart_drive.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "--clearpart all removes every partition on all disks, give --clearpart-drive or --confirm-disk-initialization"
+                ));
+            }
+            if self.zerombr && self.clearpart != ClearpartPolicy::None {
+                return Err(anyhow::anyhow!(
+                    "--zerombr with --clearpart {} wipes disks without asking, give --confirm-disk-initialization",
+                    self.clearpart.to_possible_value().unwrap().get_name()
+                ));
+            }
+        }
+
+        let mut lines = Vec::new();
+        if self.zerombr {
+            lines.push("zerombr".to_string());
+        }
+        let mut clearpart = format!("clearpart {}", self.clearpart);
+        if !self.clearpart_drive.is_empty() {
+            clearpart.push_str(&format!(" --drives={}", self.clearpart_drive.join(",")));
+        }
+        clearpart.push_str(" --initlabel");
+        lines.push(clearpart);
+        Ok(lines.join("\n"))
+    }
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct Storage {
+    #[arg(
+        long,
+        help = "Storage device, can be repeated for multiple disks",
+        default_value = "vda"
+    )]
+    storage_device: Vec<String>,
+    #[arg(long, help = "Filesystem [default: xfs, or btrfs for btrfs schemes]")]
+    filesystem: Option<String>,
+    #[arg(long, help = "Storage scheme", default_value = "lvm")]
+    #[serde(default)]
+    storage_scheme: StorageScheme,
+    #[arg(
+        long,
+        help = "Storage layout JSON file with partitions, volume groups and logical volumes"
+    )]
+    #[serde(default)]
+    storage_layout: Option<String>,
+    #[arg(
+        long,
+        help = "Volume group name of the preset layouts [default: the name used by --distro]",
+        conflicts_with = "storage_layout"
+    )]
+    #[serde(default)]
+    volume_group: Option<String>,
+    #[arg(
+        long,
+        help = "Encrypt physical volumes (data partitions without LVM) or logical volumes"
+    )]
+    #[serde(default)]
+    encrypt: Option<EncryptionTarget>,
+    #[command(flatten)]
+    #[serde(flatten)]
+    luks: LuksEncryption,
+    #[arg(
+        long,
+        help = "Swap size policy, recommended is computed from the VM memory [default: size of the layout]"
+    )]
+    #[serde(default)]
+    swap_policy: Option<SwapPolicy>,
+    #[arg(long, help = "Swap size (MiB) for the fixed swap policy")]
+    #[serde(default)]
+    swap_size: Option<u64>,
+    #[command(flatten)]
+    #[serde(flatten)]
+    initialization: DiskInitialization,
+}
+
+impl Storage {
+    /// Names the volume group of the preset layouts unless `--volume-group` is given.
+    pub fn use_volume_group(&mut self, name: &str) {
+        if self.volume_group.is_none() && self.storage_layout.is_none() {
+            self.volume_group = Some(name.to_string());
+        }
+    }
+
+    fn filesystem(&self) -> anyhow::Result<&str> {
+        let scheme = self.storage_scheme;
+        match self.filesystem.as_deref() {
+            None if scheme.is_btrfs() => Ok("btrfs"),
+            None => Ok("xfs"),
+            Some(filesystem) if scheme.is_btrfs() && filesystem != "btrfs" => Err(anyhow::anyhow!(
+                "Filesystem {filesystem} cannot be used with the {} storage scheme",
+                scheme.to_possible_value().unwrap().get_name()
+            )),
+            Some("btrfs") if !scheme.is_btrfs() => Err(anyhow::anyhow!(
+                "Filesystem btrfs requires the btrfs or autopart-btrfs storage scheme"
+            )),
+            Some(filesystem @ ("swap" | "lvmpv")) => {
+                Err(anyhow::anyhow!("{filesystem} is not a filesystem"))
+            }
+            Some(filesystem) => Ok(filesystem),
+        }
+    }
+
+    fn swap_size(&self, hardware: Option<&VmHardware>) -> anyhow::Result<Option<SwapSize>> {
+        let policy = match (self.swap_policy, self.swap_size) {
+            (None, None) => return Ok(None),
+            (None, Some(_)) => SwapPolicy::Fixed,
+            (Some(SwapPolicy::Fixed), _) => SwapPolicy::Fixed,
+            (Some(policy), Some(_)) => {
+                return Err(anyhow::anyhow!(
+                    "Swap size can only be given with the fixed swap policy, not {}",
+                    policy.to_possible_value().unwrap().get_name()
+                ))
+            }
+            (Some(policy), None) => policy,
+        };
+        Ok(Some(match policy {
+            SwapPolicy::None => SwapSize::None,
+            SwapPolicy::Recommended => match hardware {
+                Some(hardware) => SwapSize::Fixed(recommended_swap_size(hardware.memory.into())),
+                None => SwapSize::Recommended,
+            },
+            SwapPolicy::Fixed => match self.swap_size {
+                Some(0) | None => {
+                    return Err(anyhow::anyhow!(
+                        "Fixed swap policy requires a non-zero swap size"
+                    ))
+                }
+                Some(size) => SwapSize::Fixed(size),
+            },
+        }))
+    }
+
+    fn partitioning(&self, hardware: Option<&VmHardware>) -> anyhow::Result<(String, Vec<String>)> {
+        let filesystem = self.filesystem()?;
+        let default_disk = self
+            .storage_device
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("At least one storage device is required"))?;
+        let swap = self.swap_size(hardware)?;
+        if self.storage_layout.is_some() && self.storage_scheme != StorageScheme::Lvm {
+            return Err(anyhow::anyhow!(
+                "Storage layout file cannot be combined with a storage scheme"
+            ));
+        }
+        if self.storage_layout.is_some() && self.volume_group.is_some() {
+            return Err(anyhow::anyhow!(
+                "Storage layout file cannot be combined with a volume group name"
+            ));
+        }
+        if let Some(autopart_type) = self.storage_scheme.autopart_type() {
+            let mut autopart = match self.filesystem.as_deref() {
+                Some(filesystem) if autopart_type != "btrfs" => {
+                    format!("autopart --type={autopart_type} --fstype={filesystem}")
+                }
+                _ => format!("autopart --type={autopart_type}"),
+            };
+            match self.encrypt {
+                Some(EncryptionTarget::Pv) => {
+                    autopart.push_str(&format!(" {}", self.luks.generate()?));
+                }
+                Some(EncryptionTarget::Lv) => {
+                    return Err(anyhow::anyhow!(
+                        "Automatic partitioning cannot encrypt logical volumes, use --encrypt pv"
+                    ))
+                }
+                None => {}
+            }
+            match swap {
+                None | Some(SwapSize::Recommended) => {}
+                Some(SwapSize::None) => autopart.push_str(" --noswap"),
+                Some(SwapSize::Fixed(_)) => {
+                    return Err(anyhow::anyhow!(
+                        "Automatic partitioning cannot use a fixed swap size"
+                    ))
+                }
+            }
+            return Ok((autopart, self.storage_device.clone()));
+        }
+        let mut layout = match self.storage_scheme {
+            StorageScheme::ThinLvm => StorageLayout::thin_lvm(),
+            StorageScheme::Plain => StorageLayout::plain(),
+            StorageScheme::Btrfs => StorageLayout::btrfs(),
+            StorageScheme::Raid1 => match self.storage_device.as_slice() {
+                [first, second, ..] => StorageLayout::raid1(first, second),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "RAID1 storage scheme requires two storage devices"
+                    ))
+                }
+            },
+            _ => match &self.storage_layout {
+                Some(path) => StorageLayout::load(path)?,
+                None => StorageLayout::default(),
+            },
+        };
+        if let Some(name) = &self.volume_group {
+            layout.rename_volume_group(name)?;
+        }
+        if let Some(swap) = swap {
+            layout.apply_swap(swap)?;
+        }
+        if let Some(hardware) = hardware {
+            for (device, required) in layout.required_sizes(default_disk) {
+                let disk = hardware.disk(&device).ok_or_else(|| {
+                    anyhow::anyhow!("Storage layout uses {device} but the VM has no such disk")
+                })?;
+                let disk_size = u64::from(disk.size) * 1024;
+                if required > disk_size {
+                    return Err(anyhow::anyhow!(
+                        "Storage layout needs at least {required} MiB on {device} but the disk size is {} GB ({disk_size} MiB)",
+                        disk.size
+                    ));
+                }
+            }
+        }
+        match self.encrypt {
+            Some(EncryptionTarget::Pv) => layout.encrypt_partitions(),
+            Some(EncryptionTarget::Lv) => layout.encrypt_logical_volumes()?,
+            None => {}
+        }
+        let luks_options = if layout.has_encryption() {
+            Some(self.luks.generate()?)
+        } else {
+            None
+        };
+        let partitioning = layout.generate(default_disk, filesystem, luks_options.as_deref())?;
+        let disks = layout
+            .disks(default_disk)
+            .into_iter()
+            .map(|x| x.to_string())
+            .collect();
+        Ok((partitioning, disks))
+    }
+
+    pub fn generate(&self, hardware: Option<&VmHardware>) -> anyhow::Result<String> {
+        let (partitioning, used_disks) = self.partitioning(hardware)?;
+        let mut disks = self.storage_device.clone();
+        for disk in used_disks {
+            if !disks.contains(&disk) {
+                disks.push(disk);
+            }
+        }
+        if let Some(hardware) = hardware {
+            if let Some(disk) = disks.iter().find(|x| hardware.disk(x).is_none()) {
+                return Err(anyhow::anyhow!(
+                    "Storage device {disk} is not attached to the VM (attached: {})",
+                    hardware
+                        .disks
+                        .iter()
+                        .map(|x| x.device.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+            disks = hardware.disks.iter().map(|x| x.device.clone()).collect();
+        }
+        let clearpart = self.initialization.generate(&disks)?;
+        let storage_device = disks.join(",");
+        Ok(format!(
+            r#"ignoredisk --only-use={storage_device}
+# Partition clearing information
+{clearpart}
+# Disk partitioning information
+{partitioning}
+"#
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct User {
+    name: String,
+    plain: Option<String>,
+    crypt: Option<String>,
+    keyboard: bool,
+    groups: Vec<String>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    sshkeys: Vec<String>,
+    shell: Option<String>,
+    homedir: Option<String>,
+    gecos: Option<String>,
+    lock: bool,
+    system: bool,
+}
+
+const FIRST_REGULAR_GID: u32 = 1000;
+
+fn parse_id(key: &str, value: Option<String>) -> anyhow::Result<u32> {
+    let value = spec::required_value(key, value)?;
+    value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid {key}: {value}"))
+}
+
+fn check_absolute_path(key: &str, value: &str) -> anyhow::Result<()> {
+    if !value.starts_with('/') {
+        return Err(anyhow::anyhow!("{key} must be an absolute path: {value}"));
+    }
+    Ok(())
+}
+
+impl FromStr for User {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut user = User::default();
+        for (key, value) in spec::parse_spec(s)? {
+            match key.as_str() {
+                "name" => user.name = spec::required_value(&key, value)?,
+                "plain" => user.plain = Some(spec::required_value(&key, value)?),
+                "crypt" => user.crypt = Some(spec::required_value(&key, value)?),
+                "keyboard" => user.keyboard = spec::parse_bool(&key, value)?,
+                "group" => user.groups.push(spec::required_value(&key, value)?),
+                "uid" => user.uid = Some(parse_id(&key, value)?),
+                "gid" => user.gid = Some(parse_id(&key, value)?),
+                "sshkey" => user.sshkeys.push(spec::required_value(&key, value)?),
+                "shell" => user.shell = Some(spec::required_value(&key, value)?),
+                "homedir" => user.homedir = Some(spec::required_value(&key, value)?),
+                "gecos" => user.gecos = Some(spec::required_value(&key, value)?),
+                "lock" => user.lock = spec::parse_bool(&key, value)?,
+                "system" => user.system = spec::parse_bool(&key, value)?,
+                _ => return Err(anyhow::anyhow!("Unknown user option: {key}")),
+            }
+        }
+        if user.name.is_empty() {
+            return Err(anyhow::anyhow!("User name is not set"));
+        }
+        let password_sources = [user.plain.is_some(), user.crypt.is_some(), user.keyboard]
+            .iter()
+            .filter(|x| **x)
+            .count();
+        if password_sources > 1 {
+            return Err(anyhow::anyhow!(
+                "Only one of plain, crypt and keyboard can be set for user {}",
+                user.name
+            ));
+        }
+        Ok(user)
+    }
+}
+
+impl User {
+    fn is_wheel(&self) -> bool {
+        self.groups.iter().any(|x| x == "wheel")
+    }
+
+    pub fn generate(&self) -> anyhow::Result<String> {
+        let username = &self.name;
+        let sshkey: String = self
+            .sshkeys
+            .iter()
+            .map(|sshkey| format!("\nsshkey --username={username} \"{sshkey}\""))
+            .collect();
+        let pw = if let Some(pw) = &self.plain {
+            format!("--password={pw} --plaintext")
+        } else if let Some(pw) = &self.crypt {
+            format!("--password={pw} --iscrypted")
+        } else if self.keyboard {
+            let encrypt_password =
+                crate::passwd::read_and_encrypt_password(&format!("Password for {username}: "))?;
+            format!("--password={encrypt_password} --iscrypted")
+        } else if self.lock {
+            "".to_string()
+        } else {
+            return Err(anyhow::anyhow!("Password is not set for user {username}"));
+        };
+        let groups = if !self.groups.is_empty() {
+            format!("--groups={}", self.groups.join(","))
+        } else {
+            "".to_string()
+        };
+        let uid = if let Some(uid) = &self.uid {
+            format!("--uid={}", uid)
+        } else {
+            "".to_string()
+        };
+        let gid = if let Some(gid) = &self.gid {
+            format!("--gid={}", gid)
+        } else {
+            "".to_string()
+        };
+        let mut options = String::new();
+        if let Some(shell) = &self.shell {
+            check_absolute_path("shell", shell)?;
+            options.push_str(&format!(" --shell={shell}"));
+        }
+        if let Some(homedir) = &self.homedir {
+            check_absolute_path("homedir", homedir)?;
+            options.push_str(&format!(" --homedir={homedir}"));
+        }
+        if let Some(gecos) = &self.gecos {
+            if gecos.contains(':') {
+                return Err(anyhow::anyhow!("GECOS must not contain ':': {gecos}"));
+            }
+            options.push_str(&format!(" --gecos={}", quote(gecos)));
+        }
+        if self.lock {
+            options.push_str(" --lock");
+        }
+        if self.system {
+            options.push_str(" --system");
+        }
+        Ok(format!(
+            "user --name={username} {pw} {groups} {uid} {gid}{options}{sshkey}"
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct Group {
+    name: String,
+    gid: Option<u32>,
+}
+
+impl FromStr for Group {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut group = Group::default();
+        for (key, value) in spec::parse_spec(s)? {
+            match key.as_str() {
+                "name" => group.name = spec::required_value(&key, value)?,
+                "gid" => group.gid = Some(parse_id(&key, value)?),
+                _ => return Err(anyhow::anyhow!("Unknown group option: {key}")),
+            }
+        }
+        if group.name.is_empty() {
+            return Err(anyhow::anyhow!("Group name is not set"));
+        }
+        Ok(group)
+    }
+}
+
+impl Group {
+    fn generate(&self) -> String {
+        if let Some(gid) = self.gid {
+            format!("group --name={} --gid={gid}", self.name)
+        } else {
+            format!("group --name={}", self.name)
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+pub struct UserPw {
+    #[arg(long, help = "Username")]
+    username: Option<String>,
+    #[arg(long, help = "User plain password", conflicts_with_all = ["user_crypt", "user_keyboard"])]
+    user_plain: Option<String>,
+    #[arg(long, help = "User crypt password", conflicts_with_all = ["user_plain", "user_keyboard"])]
+    user_crypt: Option<String>,
     #[arg(
         long,
         help = "User password from keyboard",
@@ -215,47 +2297,106 @@ pub struct UserPw {
     user_uid: Option<u32>,
     #[arg(long, help = "User GID")]
     user_gid: Option<u32>,
+    #[arg(long, help = "User login shell (example: /sbin/nologin)")]
+    #[serde(default)]
+    user_shell: Option<String>,
+    #[arg(long, help = "User home directory")]
+    #[serde(default)]
+    user_homedir: Option<String>,
+    #[arg(long, help = "User GECOS field (full name)")]
+    #[serde(default)]
+    user_gecos: Option<String>,
+    #[arg(long, help = "Lock the user account")]
+    #[serde(default)]
+    user_lock: bool,
+    #[arg(long, help = "Create the user as a system account")]
+    #[serde(default)]
+    user_system: bool,
+    #[arg(
+        long,
+        help = "Additional user, can be repeated (example: name=app,crypt=$6$...,group=wheel,uid=5001,sshkey=\"ssh-ed25519 ...\")"
+    )]
+    #[serde(default)]
+    user: Vec<User>,
+    #[arg(long, help = "Group, can be repeated (example: name=app,gid=5001)")]
+    #[serde(default)]
+    group: Vec<Group>,
 }
 
 impl UserPw {
-    pub fn generate(&self) -> anyhow::Result<String> {
+    fn users(&self) -> Vec<User> {
+        let mut users = Vec::new();
         if let Some(username) = &self.username {
-            let sshkey = if let Some(sshkey) = &self.user_sshkey {
-                format!("\nsshkey --username={username} \"{sshkey}\"")
-            } else {
-                "".to_string()
-            };
-            let pw = if let Some(pw) = &self.user_plain {
-                format!("--password={pw} --plaintext")
-            } else if let Some(pw) = &self.user_crypt {
-                format!("--password={pw} --iscrypted")
-            } else if self.user_keyboard {
-                let encrypt_password = crate::passwd::read_and_encrypt_password("User Password: ")?;
-                format!("--password={encrypt_password} --iscrypted")
-            } else {
-                return Err(anyhow::anyhow!("User password is not set"));
-            };
-            let groups = if let Some(groups) = self.user_groups.as_ref() {
-                format!("--groups={}", groups.join(","))
-            } else {
-                "".to_string()
-            };
-            let uid = if let Some(uid) = &self.user_uid {
-                format!("--uid={}", uid)
-            } else {
-                "".to_string()
-            };
-            let gid = if let Some(gid) = &self.user_gid {
-                format!("--gid={}", gid)
-            } else {
-                "".to_string()
-            };
-            Ok(format!(
-                "user --name={username} {pw} {groups} {uid} {gid}{sshkey}"
-            ))
-        } else {
-            Ok("".to_string())
+            users.push(User {
+                name: username.clone(),
+                plain: self.user_plain.clone(),
+                crypt: self.user_crypt.clone(),
+                keyboard: self.user_keyboard,
+                groups: self.user_groups.clone().unwrap_or_default(),
+                uid: self.user_uid,
+                gid: self.user_gid,
+                sshkeys: self.user_sshkey.iter().cloned().collect(),
+                shell: self.user_shell.clone(),
+                homedir: self.user_homedir.clone(),
+                gecos: self.user_gecos.clone(),
+                lock: self.user_lock,
+                system: self.user_system,
+            });
         }
+        users.extend(self.user.iter().cloned());
+        users
+    }
+
+    fn groups(&self) -> anyhow::Result<Vec<Group>> {
+        let mut groups = self.group.clone();
+        let mut names = HashSet::new();
+        let mut gids = HashSet::new();
+        for group in &groups {
+            if !names.insert(group.name.clone()) {
+                return Err(anyhow::anyhow!("Duplicated group: {}", group.name));
+            }
+            if let Some(gid) = group.gid {
+                if !gids.insert(gid) {
+                    return Err(anyhow::anyhow!("Duplicated group GID: {gid}"));
+                }
+            }
+        }
+        // A primary GID has to exist before the user is created. GIDs below
+        // FIRST_REGULAR_GID are expected to exist in the installed system, such as
+        // users (100), and have to be declared with --group otherwise.
+        for user in self.users() {
+            if let Some(gid) = user.gid.filter(|x| *x >= FIRST_REGULAR_GID) {
+                if gids.insert(gid) {
+                    if !names.insert(user.name.clone()) {
+                        return Err(anyhow::anyhow!(
+                            "Group {} is declared without GID {gid}",
+                            user.name
+                        ));
+                    }
+                    groups.push(Group {
+                        name: user.name,
+                        gid: Some(gid),
+                    });
+                }
+            }
+        }
+        Ok(groups)
+    }
+
+    pub fn generate(&self) -> anyhow::Result<String> {
+        let users = self.users();
+        let mut names = HashSet::new();
+        let mut lines: Vec<_> = self.groups()?.iter().map(|x| x.generate()).collect();
+        for user in &users {
+            if user.name == "root" {
+                return Err(anyhow::anyhow!("Use the root password options for root"));
+            }
+            if !names.insert(user.name.as_str()) {
+                return Err(anyhow::anyhow!("Duplicated user: {}", user.name));
+            }
+            lines.push(user.generate()?);
+        }
+        Ok(lines.join("\n"))
     }
 }
 
@@ -267,28 +2408,128 @@ pub struct Kickstart {
     #[command(flatten, next_help_heading = "Kickstart System")]
     #[serde(flatten)]
     system_options: KickstartSystem,
+    #[command(flatten, next_help_heading = "Kickstart Addons")]
+    #[serde(flatten)]
+    addons: KickstartAddons,
+    #[command(flatten, next_help_heading = "Kickstart Install Source")]
+    #[serde(flatten)]
+    install_source: InstallSource,
     #[command(flatten, next_help_heading = "Kickstart Root Password")]
     #[serde(flatten)]
     rootpw: RootPw,
     #[command(flatten, next_help_heading = "Kickstart Storage")]
     #[serde(flatten)]
     storage: Storage,
+    #[command(flatten, next_help_heading = "Kickstart Bootloader")]
+    #[serde(flatten)]
+    bootloader: Bootloader,
     #[command(flatten, next_help_heading = "Kickstart User")]
     #[serde(flatten)]
     user: UserPw,
+    #[command(flatten, next_help_heading = "Kickstart Sudo")]
+    #[serde(flatten)]
+    sudo: SudoPolicy,
+    #[command(flatten, next_help_heading = "Kickstart SSH Server")]
+    #[serde(flatten)]
+    sshd: SshdConfig,
+    #[command(flatten, next_help_heading = "Kickstart Scripts")]
+    #[serde(flatten)]
+    scripts: KickstartScripts,
 }
 
 impl Kickstart {
     pub fn run(&self) -> anyhow::Result<()> {
-        println!("{}", self.generate()?);
+        println!("{}", self.generate(None)?);
         Ok(())
     }
 
-    pub fn generate(&self) -> anyhow::Result<String> {
-        let network = self.network.generate();
-        let rootpw = self.rootpw.generate()?;
-        let storage = self.storage.generate();
+    pub fn use_install_location(&mut self, location: &str) {
+        self.install_source.use_install_location(location);
+    }
+
+    pub fn initrd_files(&self) -> Vec<String> {
+        self.addons.initrd_files()
+    }
+
+    pub fn reboots_after_install(&self) -> bool {
+        self.system_options.completion_action == CompletionAction::Reboot
+    }
+
+    /// A halted guest is not powered off, so virt-install never sees it shut down.
+    pub fn halts_after_install(&self) -> bool {
+        self.system_options.completion_action == CompletionAction::Halt
+    }
+
+    pub fn network_interface_count(&self) -> usize {
+        self.network.physical_devices().len()
+    }
+
+    /// `--osinfo` of virt-install for the target release.
+    pub fn osinfo(&self) -> anyhow::Result<String> {
+        Ok(self.system_options.profile()?.osinfo())
+    }
+
+    /// Warnings for the target release: an end-of-life release, DNS search domains
+    /// before release 9 and the deprecated OpenSCAP addon name.
+    fn warnings(&self, profile: &ReleaseProfile) -> Vec<String> {
+        let mut warnings = profile.warnings();
+        if !profile.has_dns_search()
+            && self
+                .network
+                .interfaces()
+                .iter()
+                .any(|x| !x.dns_search.is_empty())
+        {
+            warnings.push(format!(
+                "network --ipv4-dns-search needs release 8.7 or Fedora 36 and later, the {profile} installer may reject it"
+            ));
+        }
+        let oscap_addon = profile.oscap_addon();
+        for addon in &self.addons.addon {
+            if addon.name() == "org_fedora_oscap" && oscap_addon != addon.name() {
+                warnings.push(format!(
+                    "%addon org_fedora_oscap is deprecated on {profile}, use {oscap_addon}"
+                ));
+            }
+        }
+        warnings
+    }
+
+    pub fn generate(&self, hardware: Option<&VmHardware>) -> anyhow::Result<String> {
+        let profile = self.system_options.profile()?;
+        if self.storage.storage_scheme.is_btrfs() && !profile.is_fedora() {
+            return Err(anyhow::anyhow!(
+                "Btrfs is not supported by the {profile} installer"
+            ));
+        }
+        for warning in self.warnings(&profile) {
+            eprintln!("Warning: {warning}");
+        }
+        let network = self.network.generate()?;
+        let system = self.system_options.generate()?;
+        let (repos, source) = self.install_source.generate(&profile)?;
+        let addons = self
+            .addons
+            .generate(hardware, &profile)?
+            .iter()
+            .map(|x| x.generate())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        // rootpw --allow-ssh writes its own sshd drop-in with PermitRootLogin yes,
+        // which would take precedence over other --sshd-permit-root-login values
+        let rootpw = self
+            .rootpw
+            .generate(profile.root_ssh_disabled_by_default() && self.sshd.root_login_allowed())?;
+        let mut storage = self.storage.clone();
+        storage.use_volume_group(profile.volume_group());
+        let storage = storage.generate(hardware)?;
+        let bootloader = self.bootloader.generate()?;
         let user = self.user.generate()?;
+        let configuration: Vec<_> = [self.sudo.generate()?, self.sshd.generate(&profile)?]
+            .into_iter()
+            .flatten()
+            .collect();
+        let scripts = self.scripts.generate(&configuration)?;
         let install_mode = if self.network.text {
             "text"
         } else {
@@ -297,36 +2538,41 @@ impl Kickstart {
         let keyboard = &self.system_options.keyboard;
         let language = &self.system_options.language;
         let environment = &self.system_options.environment;
-        let timezone = &self.system_options.timezone;
-        let packages = self.system_options.packages.join("\n");
+        if !profile.has_environment(&environment.to_string()) {
+            return Err(anyhow::anyhow!(
+                "Environment {environment} is not available on {profile}"
+            ));
+        }
+        let timezone = self.system_options.time()?;
+        let completion_action = self.system_options.completion_action;
+        let (package_options, packages) = self.system_options.packages(&self.sshd.packages())?;
 
-        if self.rootpw.rootpw_locked && self.user.username.is_none() {
+        let users = self.user.users();
+        if self.rootpw.rootpw_locked && users.is_empty() {
             return Err(anyhow::anyhow!(
                 "Root password is locked and user is not set"
             ));
         }
-        if self.rootpw.rootpw_locked
-            && !self
-                .user
-                .user_groups
-                .as_ref()
-                .map(|x| x.contains(&"wheel".to_string()))
-                .unwrap_or(false)
+        if self.rootpw.rootpw_locked && !users.iter().any(|x| x.is_wheel()) {
+            return Err(anyhow::anyhow!(
+                "Root password is locked and no user is in wheel group"
+            ));
+        }
+        if self.sshd.password_auth_disabled()
+            && (self.rootpw.root_sshkey.is_none() || self.sshd.root_login_disabled())
+            && users.iter().all(|x| x.sshkeys.is_empty())
         {
             return Err(anyhow::anyhow!(
-                "Root password is locked and user is not in wheel group"
+                "SSH password authentication is disabled but no SSH key is installed"
             ));
         }
 
         Ok(format!(
             r#"{install_mode}
 eula --agreed
-repo --name="AppStream" --baseurl=file:///run/install/sources/mount-0000-cdrom/AppStream
-reboot
+{repos}
 
-%addon com_redhat_kdump --enable --reserve-mb='auto'
-
-%end
+{addons}
 
 # Keyboard layouts
 keyboard --xlayouts='{keyboard}'
@@ -336,10 +2582,10 @@ lang {language}
 # Network information
 {network}
 
-# Use CDROM installation media
-cdrom
+# Installation source
+{source}
 
-%packages
+%packages{package_options}
 @^{environment}
 {packages}
 
@@ -351,15 +2597,22 @@ firstboot --enable
 # Disk
 {storage}
 
+# Bootloader
+{bootloader}
+
 # System timezone
-timezone {timezone} --utc
+{timezone}
+
+# SELinux, firewall and services
+{system}
 
 #Root password
 {rootpw}
 {user}
 
-shutdown
-"#
+{completion_action}
+
+{scripts}"#
         ))
     }
 }
@@ -367,6 +2620,19 @@ shutdown
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::createvm::AttachedDisk;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct KickstartParser {
+        #[command(flatten)]
+        kickstart: Kickstart,
+    }
+
+    fn parse_kickstart(args: &[&str]) -> Kickstart {
+        let base = ["kickstart", "--rootpw-plain", "password"];
+        KickstartParser::parse_from(base.iter().chain(args.iter())).kickstart
+    }
 
     #[test]
     fn test_kickstart_network_dhcp() {
@@ -377,11 +2643,23 @@ mod test {
             network_ip: "10.0.0.2".to_string(),
             network_netmask: "255.255.0.0".to_string(),
             network_gateway: "10.0.0.254".to_string(),
-            network_nameserver: "10.0.0.1".to_string(),
+            network_nameserver: vec!["10.0.0.1".to_string()],
+            network_dns_search: vec![],
             network_hostname: "test.example.com".to_string(),
+            network_ipv6: Ipv6Mode::Auto,
+            network_ipv6_address: None,
+            network_ipv6_gateway: None,
+            network_ipv6_nameserver: vec![],
+            network_bond_slaves: vec![],
+            network_bond_mode: None,
+            network_bond_opts: vec![],
+            network_vlan_id: None,
+            network_bridge_slaves: vec![],
+            network_no_activate: false,
+            network_interface: vec![],
         };
         assert_eq!(
-            network.generate(),
+            network.generate().unwrap(),
             r#"network --bootproto=dhcp --device=enp1s0 --hostname=test.example.com --ipv6=auto --activate"#
         );
     }
@@ -395,23 +2673,186 @@ mod test {
             network_ip: "10.0.0.2".to_string(),
             network_netmask: "255.255.0.0".to_string(),
             network_gateway: "10.0.0.254".to_string(),
-            network_nameserver: "10.0.0.1".to_string(),
+            network_nameserver: vec!["10.0.0.1".to_string()],
+            network_dns_search: vec![],
             network_hostname: "test.example.com".to_string(),
+            network_ipv6: Ipv6Mode::Auto,
+            network_ipv6_address: None,
+            network_ipv6_gateway: None,
+            network_ipv6_nameserver: vec![],
+            network_bond_slaves: vec![],
+            network_bond_mode: None,
+            network_bond_opts: vec![],
+            network_vlan_id: None,
+            network_bridge_slaves: vec![],
+            network_no_activate: false,
+            network_interface: vec![],
         };
         assert_eq!(
-            network.generate(),
+            network.generate().unwrap(),
             r#"network --bootproto=static --ip=10.0.0.2 --netmask=255.255.0.0 --gateway=10.0.0.254 --device=enp1s0 --nameserver=10.0.0.1 --hostname=test.example.com --ipv6=auto --activate"#
         );
     }
 
+    #[test]
+    fn test_kickstart_network_multiple() {
+        let network = KickstartNetwork {
+            text: false,
+            network_device: "enp1s0".to_string(),
+            network_bootproto: NetworkBootproto::Dhcp,
+            network_ip: "10.0.0.2".to_string(),
+            network_netmask: "255.255.0.0".to_string(),
+            network_gateway: "10.0.0.254".to_string(),
+            network_nameserver: vec!["10.0.0.1".to_string()],
+            network_dns_search: vec![],
+            network_hostname: "test.example.com".to_string(),
+            network_ipv6: Ipv6Mode::Auto,
+            network_ipv6_address: None,
+            network_ipv6_gateway: None,
+            network_ipv6_nameserver: vec![],
+            network_bond_slaves: vec![],
+            network_bond_mode: None,
+            network_bond_opts: vec![],
+            network_vlan_id: None,
+            network_bridge_slaves: vec![],
+            network_no_activate: false,
+            network_interface: vec![
+                "device=enp2s0,bootproto=static,ip=10.1.0.2,netmask=255.255.255.0,activate=no"
+                    .parse()
+                    .unwrap(),
+                "device=enp3s0".parse().unwrap(),
+            ],
+        };
+        assert_eq!(
+            network.generate().unwrap(),
+            r#"network --bootproto=dhcp --device=enp1s0 --hostname=test.example.com --ipv6=auto --activate
+network --bootproto=static --ip=10.1.0.2 --netmask=255.255.255.0 --device=enp2s0 --ipv6=auto
+network --bootproto=dhcp --device=enp3s0 --ipv6=auto --activate"#
+        );
+
+        let mut duplicated = network.clone();
+        duplicated.network_interface = vec!["device=enp1s0".parse().unwrap()];
+        assert!(duplicated.generate().is_err());
+        assert!("bootproto=dhcp".parse::<NetworkInterface>().is_err());
+    }
+
+    #[test]
+    fn test_kickstart_network_ipv6() {
+        let interface: NetworkInterface = "device=enp1s0,bootproto=static,ip=10.0.0.2,netmask=255.255.255.0,ipv6=static,ipv6-address=2001:db8::2/64,ipv6-gateway=2001:db8::1,ipv6-nameserver=2001:db8::53"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            interface.generate(None).unwrap(),
+            r#"network --bootproto=static --ip=10.0.0.2 --netmask=255.255.255.0 --device=enp1s0 --nameserver=2001:db8::53 --ipv6=2001:db8::2/64 --ipv6gateway=2001:db8::1 --activate"#
+        );
+
+        let interface: NetworkInterface = "device=enp1s0,ipv6=disabled".parse().unwrap();
+        assert_eq!(
+            interface.generate(None).unwrap(),
+            r#"network --bootproto=dhcp --device=enp1s0 --noipv6 --activate"#
+        );
+
+        let missing_prefix: NetworkInterface = "device=enp1s0,ipv6=static,ipv6-address=2001:db8::2"
+            .parse()
+            .unwrap();
+        assert!(missing_prefix.generate(None).is_err());
+        let address_without_static: NetworkInterface =
+            "device=enp1s0,ipv6-address=2001:db8::2/64".parse().unwrap();
+        assert!(address_without_static.generate(None).is_err());
+    }
+
+    #[test]
+    fn test_kickstart_network_bond_vlan_bridge() {
+        let mut network = KickstartNetwork {
+            text: false,
+            network_device: "bond0".to_string(),
+            network_bootproto: NetworkBootproto::Dhcp,
+            network_ip: "10.0.0.2".to_string(),
+            network_netmask: "255.255.0.0".to_string(),
+            network_gateway: "10.0.0.254".to_string(),
+            network_nameserver: vec!["10.0.0.1".to_string()],
+            network_dns_search: vec![],
+            network_hostname: "test.example.com".to_string(),
+            network_ipv6: Ipv6Mode::Auto,
+            network_ipv6_address: None,
+            network_ipv6_gateway: None,
+            network_ipv6_nameserver: vec![],
+            network_bond_slaves: vec!["enp1s0".to_string(), "enp2s0".to_string()],
+            network_bond_mode: Some("active-backup".to_string()),
+            network_bond_opts: vec!["miimon=100".to_string()],
+            network_vlan_id: None,
+            network_bridge_slaves: vec![],
+            network_no_activate: false,
+            network_interface: vec![
+                "device=bond0,vlan-id=100,activate=no".parse().unwrap(),
+                "device=br0,bridge-slave=enp3s0".parse().unwrap(),
+            ],
+        };
+        assert_eq!(
+            network.generate().unwrap(),
+            r#"network --bootproto=dhcp --device=bond0 --bondslaves=enp1s0,enp2s0 --bondopts=mode=active-backup,miimon=100 --hostname=test.example.com --ipv6=auto --activate
+network --bootproto=dhcp --device=bond0 --vlanid=100 --ipv6=auto
+network --bootproto=dhcp --device=br0 --bridgeslaves=enp3s0 --ipv6=auto --activate"#
+        );
+        assert_eq!(
+            network.physical_devices(),
+            vec![
+                "enp1s0".to_string(),
+                "enp2s0".to_string(),
+                "enp3s0".to_string()
+            ]
+        );
+
+        network.network_interface = vec!["device=enp2s0".parse().unwrap()];
+        assert!(network.generate().is_err());
+        network.network_interface = vec!["device=br0,bridge-slave=enp1s0".parse().unwrap()];
+        assert!(network.generate().is_err());
+        network.network_interface = vec!["device=enp3s0,vlan-id=4095".parse().unwrap()];
+        assert!(network.generate().is_err());
+        network.network_interface = vec![];
+        network.network_bond_mode = Some("fastest".to_string());
+        assert!(network.generate().is_err());
+    }
+
+    #[test]
+    fn test_kickstart_network_cidr() {
+        let interface: NetworkInterface = "device=enp1s0,bootproto=static,ip=10.0.0.5/24,gateway=10.0.0.1,nameserver=10.0.0.53,nameserver=10.0.1.53,dns-search=example.com,dns-search=corp.example.com"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            interface.generate(None).unwrap(),
+            r#"network --bootproto=static --ip=10.0.0.5 --netmask=255.255.255.0 --gateway=10.0.0.1 --device=enp1s0 --nameserver=10.0.0.53,10.0.1.53 --ipv4-dns-search=example.com,corp.example.com --ipv6=auto --activate"#
+        );
+
+        for invalid in [
+            "device=enp1s0,bootproto=static,ip=10.0.0.5/33",
+            "device=enp1s0,bootproto=static,ip=10.0.0.256/24",
+            "device=enp1s0,bootproto=static,ip=10.0.0.5,netmask=255.0.255.0",
+            "device=enp1s0,bootproto=static,ip=10.0.0.5/24,gateway=10.0.1.1",
+            "device=enp1s0,bootproto=static,ip=10.0.0.5/24,nameserver=dns.example.com",
+            "device=enp1s0,dns-search=-invalid.example.com",
+        ] {
+            let interface: NetworkInterface = invalid.parse().unwrap();
+            assert!(interface.generate(None).is_err(), "{invalid}");
+        }
+    }
+
     #[test]
     fn test_kickstart_storage() {
         let storage = Storage {
-            storage_device: "sda1".to_string(),
-            filesystem: "ext4".to_string(),
+            storage_device: vec!["sda1".to_string()],
+            filesystem: Some("ext4".to_string()),
+            storage_scheme: StorageScheme::Lvm,
+            storage_layout: None,
+            volume_group: None,
+            encrypt: None,
+            luks: LuksEncryption::default(),
+            swap_policy: None,
+            swap_size: None,
+            initialization: DiskInitialization::default(),
         };
         assert_eq!(
-            storage.generate(),
+            storage.generate(None).unwrap(),
             r#"ignoredisk --only-use=sda1
 # Partition clearing information
 clearpart --none --initlabel
@@ -426,6 +2867,173 @@ logvol / --fstype="ext4" --size=10240 --name=root --vgname=almalinux --grow
         );
     }
 
+    #[test]
+    fn test_kickstart_storage_scheme() {
+        let mut storage = Storage {
+            storage_device: vec!["vda".to_string()],
+            filesystem: None,
+            storage_scheme: StorageScheme::AutopartThinp,
+            storage_layout: None,
+            volume_group: None,
+            encrypt: None,
+            luks: LuksEncryption::default(),
+            swap_policy: None,
+            swap_size: None,
+            initialization: DiskInitialization::default(),
+        };
+        assert_eq!(
+            storage.generate(None).unwrap(),
+            r#"ignoredisk --only-use=vda
+# Partition clearing information
+clearpart --none --initlabel
+# Disk partitioning information
+autopart --type=thinp
+"#
+        );
+
+        storage.storage_scheme = StorageScheme::Btrfs;
+        assert!(storage
+            .generate(None)
+            .unwrap()
+            .contains("btrfs none --label=root btrfs.01"));
+        storage.filesystem = Some("ext4".to_string());
+        assert!(storage.generate(None).is_err());
+        storage.storage_scheme = StorageScheme::AutopartBtrfs;
+        assert!(storage.generate(None).is_err());
+        storage.storage_scheme = StorageScheme::Lvm;
+        storage.filesystem = Some("btrfs".to_string());
+        assert!(storage.generate(None).is_err());
+        storage.filesystem = None;
+        storage.storage_scheme = StorageScheme::Plain;
+        storage.storage_layout = Some("testdata/layout.json".to_string());
+        assert!(storage.generate(None).is_err());
+    }
+
+    #[test]
+    fn test_kickstart_storage_encryption() {
+        let mut storage = Storage {
+            storage_device: vec!["vda".to_string()],
+            filesystem: None,
+            storage_scheme: StorageScheme::AutopartLvm,
+            storage_layout: None,
+            volume_group: None,
+            encrypt: Some(EncryptionTarget::Pv),
+            luks: LuksEncryption {
+                luks_version: Some(LuksVersion::Luks2),
+                luks_passphrase_plain: Some("pass \"phrase\"".to_string()),
+                ..Default::default()
+            },
+            swap_policy: None,
+            swap_size: None,
+            initialization: DiskInitialization::default(),
+        };
+        assert!(storage.generate(None).unwrap().contains(
+            r#"autopart --type=lvm --encrypted --passphrase="pass \"phrase\"" --luks-version=luks2"#
+        ));
+
+        storage.storage_scheme = StorageScheme::Lvm;
+        storage.encrypt = Some(EncryptionTarget::Lv);
+        assert!(storage.generate(None).unwrap().contains(
+            r#"logvol / --fstype="xfs" --size=10240 --name=root --vgname=almalinux --grow --encrypted"#
+        ));
+
+        storage.luks.luks_passphrase_plain = None;
+        assert!(storage.generate(None).is_err());
+    }
+
+    #[test]
+    fn test_kickstart_storage_swap() {
+        let mut storage = Storage {
+            storage_device: vec!["vda".to_string()],
+            filesystem: None,
+            storage_scheme: StorageScheme::Lvm,
+            storage_layout: None,
+            volume_group: None,
+            encrypt: None,
+            luks: LuksEncryption::default(),
+            swap_policy: Some(SwapPolicy::Recommended),
+            swap_size: None,
+            initialization: DiskInitialization::default(),
+        };
+        let hardware = VmHardware {
+            memory: 2048,
+            disks: vec![AttachedDisk {
+                device: "vda".to_string(),
+                size: 20,
+            }],
+        };
+        assert!(storage
+            .generate(Some(&hardware))
+            .unwrap()
+            .contains(r#"logvol swap --fstype="swap" --size=4096 --name=swap"#));
+        assert!(storage
+            .generate(None)
+            .unwrap()
+            .contains(r#"logvol swap --fstype="swap" --recommended --name=swap"#));
+
+        storage.swap_policy = Some(SwapPolicy::Fixed);
+        assert!(storage.generate(None).is_err());
+        storage.swap_size = Some(30720);
+        assert!(storage.generate(None).is_ok());
+        assert!(storage.generate(Some(&hardware)).is_err());
+
+        storage.swap_policy = Some(SwapPolicy::None);
+        assert!(storage.generate(None).is_err());
+        storage.swap_size = None;
+        storage.storage_scheme = StorageScheme::AutopartLvm;
+        assert!(storage
+            .generate(None)
+            .unwrap()
+            .contains("autopart --type=lvm --noswap"));
+    }
+
+    #[test]
+    fn test_kickstart_storage_multiple_disks() {
+        let mut storage = Storage {
+            storage_device: vec!["vda".to_string(), "vdb".to_string()],
+            filesystem: None,
+            storage_scheme: StorageScheme::Raid1,
+            storage_layout: None,
+            volume_group: None,
+            encrypt: None,
+            luks: LuksEncryption::default(),
+            swap_policy: None,
+            swap_size: None,
+            initialization: DiskInitialization::default(),
+        };
+        let mut hardware = VmHardware {
+            memory: 2048,
+            disks: vec![
+                AttachedDisk {
+                    device: "vda".to_string(),
+                    size: 20,
+                },
+                AttachedDisk {
+                    device: "vdb".to_string(),
+                    size: 20,
+                },
+                AttachedDisk {
+                    device: "vdc".to_string(),
+                    size: 100,
+                },
+            ],
+        };
+        let generated = storage.generate(Some(&hardware)).unwrap();
+        assert!(generated.starts_with("ignoredisk --only-use=vda,vdb,vdc\n"));
+        assert!(generated.contains("raid /boot --device=boot"));
+        assert!(storage
+            .generate(None)
+            .unwrap()
+            .starts_with("ignoredisk --only-use=vda,vdb\n"));
+
+        hardware.disks[1].size = 10;
+        assert!(storage.generate(Some(&hardware)).is_err());
+        hardware.disks.truncate(1);
+        assert!(storage.generate(Some(&hardware)).is_err());
+        storage.storage_device.truncate(1);
+        assert!(storage.generate(None).is_err());
+    }
+
     #[test]
     fn test_kickstart_rootpw_plain() {
         let rootpw = RootPw {
@@ -436,7 +3044,7 @@ logvol / --fstype="ext4" --size=10240 --name=root --vgname=almalinux --grow
             root_sshkey: Some("SSHKEY".to_string()),
         };
         assert_eq!(
-            rootpw.generate().unwrap(),
+            rootpw.generate(false).unwrap(),
             r#"rootpw --plaintext password
 sshkey --username=root "SSHKEY""#
         );
@@ -451,7 +3059,10 @@ sshkey --username=root "SSHKEY""#
             rootpw_locked: false,
             root_sshkey: None,
         };
-        assert_eq!(rootpw.generate().unwrap(), r#"rootpw --iscrypted CRYPT"#);
+        assert_eq!(
+            rootpw.generate(false).unwrap(),
+            r#"rootpw --iscrypted CRYPT"#
+        );
     }
 
     #[test]
@@ -463,7 +3074,7 @@ sshkey --username=root "SSHKEY""#
             rootpw_locked: true,
             root_sshkey: None,
         };
-        assert_eq!(rootpw.generate().unwrap(), r#"rootpw --lock"#);
+        assert_eq!(rootpw.generate(false).unwrap(), r#"rootpw --lock"#);
     }
 
     #[test]
@@ -477,6 +3088,13 @@ sshkey --username=root "SSHKEY""#
             user_gid: None,
             user_groups: None,
             user_sshkey: None,
+            user_shell: None,
+            user_homedir: None,
+            user_gecos: None,
+            user_lock: false,
+            user_system: false,
+            user: vec![],
+            group: vec![],
         };
         assert_eq!(
             userpw.generate().unwrap(),
@@ -495,11 +3113,748 @@ sshkey --username=root "SSHKEY""#
             user_gid: Some(6000),
             user_groups: Some(["wheel".to_string(), "docker".to_string()].to_vec()),
             user_sshkey: Some("SSHKEY".to_string()),
+            user_shell: None,
+            user_homedir: None,
+            user_gecos: None,
+            user_lock: false,
+            user_system: false,
+            user: vec![],
+            group: vec![],
         };
         assert_eq!(
             userpw.generate().unwrap(),
-            r#"user --name=testuser --password=CRYPT --iscrypted --groups=wheel,docker --uid=5000 --gid=6000
+            r#"group --name=testuser --gid=6000
+user --name=testuser --password=CRYPT --iscrypted --groups=wheel,docker --uid=5000 --gid=6000
 sshkey --username=testuser "SSHKEY""#
         );
     }
+
+    #[test]
+    fn test_kickstart_multiple_users() {
+        let userpw = UserPw {
+            username: Some("ops".to_string()),
+            user_plain: None,
+            user_crypt: Some("CRYPT".to_string()),
+            user_keyboard: false,
+            user_uid: None,
+            user_gid: None,
+            user_groups: Some(vec!["wheel".to_string()]),
+            user_sshkey: Some("SSHKEY".to_string()),
+            user_shell: None,
+            user_homedir: None,
+            user_gecos: None,
+            user_lock: false,
+            user_system: false,
+            user: vec![
+                r#"name=app,plain=secret,group=app,group=docker,uid=5001,gid=5001,sshkey="ssh-ed25519 KEY1 a",sshkey="ssh-ed25519 KEY2 b""#
+                    .parse()
+                    .unwrap(),
+            ],
+            group: vec![],
+        };
+        assert_eq!(
+            userpw.generate().unwrap(),
+            r#"group --name=app --gid=5001
+user --name=ops --password=CRYPT --iscrypted --groups=wheel  
+sshkey --username=ops "SSHKEY"
+user --name=app --password=secret --plaintext --groups=app,docker --uid=5001 --gid=5001
+sshkey --username=app "ssh-ed25519 KEY1 a"
+sshkey --username=app "ssh-ed25519 KEY2 b""#
+        );
+        assert!(userpw.users()[0].is_wheel());
+        assert!(!userpw.users()[1].is_wheel());
+
+        let mut duplicated = userpw.clone();
+        duplicated.user.push("name=ops,plain=x".parse().unwrap());
+        assert!(duplicated.generate().is_err());
+        assert!("name=app,plain=x,crypt=y".parse::<User>().is_err());
+        assert!("plain=x".parse::<User>().is_err());
+        assert!("name=app,uid=abc".parse::<User>().is_err());
+    }
+
+    #[test]
+    fn test_kickstart_user_attributes() {
+        let userpw = UserPw {
+            username: Some("ops".to_string()),
+            user_plain: Some("password".to_string()),
+            user_crypt: None,
+            user_keyboard: false,
+            user_uid: None,
+            user_gid: Some(5000),
+            user_groups: None,
+            user_sshkey: None,
+            user_shell: Some("/bin/zsh".to_string()),
+            user_homedir: None,
+            user_gecos: Some("Operations \"Team\"".to_string()),
+            user_lock: false,
+            user_system: false,
+            user: vec![
+                "name=svc,lock,system,shell=/sbin/nologin,homedir=/var/lib/svc,uid=990,gid=990"
+                    .parse()
+                    .unwrap(),
+                "name=batch,lock,gid=5000".parse().unwrap(),
+            ],
+            group: vec![
+                "name=operators,gid=5000".parse().unwrap(),
+                "name=svc,gid=990".parse().unwrap(),
+            ],
+        };
+        assert_eq!(
+            userpw.generate().unwrap(),
+            r#"group --name=operators --gid=5000
+group --name=svc --gid=990
+user --name=ops --password=password --plaintext   --gid=5000 --shell=/bin/zsh --gecos="Operations \"Team\""
+user --name=svc   --uid=990 --gid=990 --shell=/sbin/nologin --homedir=/var/lib/svc --lock --system
+user --name=batch    --gid=5000 --lock"#
+        );
+
+        let mut invalid = userpw.clone();
+        invalid.user_shell = Some("zsh".to_string());
+        assert!(invalid.generate().is_err());
+        let mut invalid = userpw.clone();
+        invalid.group.push("name=svc".parse().unwrap());
+        assert!(invalid.generate().is_err());
+        let mut invalid = userpw.clone();
+        invalid.group.push("name=others,gid=5000".parse().unwrap());
+        assert!(invalid.generate().is_err());
+
+        // System GIDs already exist and are not created for the user
+        let mut system_gid = userpw.clone();
+        system_gid.group.clear();
+        system_gid.user = vec!["name=svc,lock,gid=100".parse().unwrap()];
+        let generated = system_gid.generate().unwrap();
+        assert!(generated.starts_with("group --name=ops --gid=5000\n"));
+        assert!(!generated.contains("--gid=100\n"));
+        assert!(!generated.contains("group --name=svc"));
+        assert!(generated.contains("user --name=svc    --gid=100 --lock"));
+        assert!("name=svc,system"
+            .parse::<User>()
+            .unwrap()
+            .generate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_kickstart_sshd() {
+        let kickstart = parse_kickstart(&[
+            "--username",
+            "ops",
+            "--user-crypt",
+            "CRYPT",
+            "--user-sshkey",
+            "SSHKEY",
+            "--sshd-disable-password-auth",
+            "--sshd-permit-root-login",
+            "no",
+            "--sshd-port",
+            "2222",
+        ]);
+        let generated = kickstart.generate(None).unwrap();
+        assert!(generated.contains("\npolicycoreutils-python-utils\n"));
+        assert!(generated.contains("/etc/ssh/sshd_config.d/10-unattended-kvm-install.conf"));
+
+        let kickstart = parse_kickstart(&[
+            "--root-sshkey",
+            "SSHKEY",
+            "--sshd-disable-password-auth",
+            "--sshd-permit-root-login",
+            "no",
+        ]);
+        assert!(kickstart.generate(None).is_err());
+    }
+
+    #[test]
+    fn test_kickstart_system() {
+        let kickstart = parse_kickstart(&[
+            "--selinux",
+            "enforcing",
+            "--firewall-service",
+            "ssh",
+            "--firewall-service",
+            "https",
+            "--firewall-port",
+            "8080/tcp",
+            "--firewall-port",
+            "60000-61000:udp",
+            "--enable-service",
+            "cockpit.socket",
+            "--disable-service",
+            "kdump",
+        ]);
+        assert_eq!(
+            kickstart.system_options.generate().unwrap(),
+            r#"selinux --enforcing
+firewall --enabled --service=ssh,https --port=8080:tcp,60000-61000:udp
+services --disabled=kdump --enabled=cockpit.socket"#
+        );
+        let kickstart = parse_kickstart(&["--firewall-disabled"]);
+        assert_eq!(
+            kickstart.system_options.generate().unwrap(),
+            "firewall --disabled"
+        );
+        assert_eq!(parse_kickstart(&[]).system_options.generate().unwrap(), "");
+
+        for args in [
+            ["--firewall-port", "8080"],
+            ["--firewall-port", "0/tcp"],
+            ["--firewall-port", "80/icmp"],
+            ["--firewall-service", "ssh,https"],
+            ["--enable-service", "a b"],
+        ] {
+            assert!(parse_kickstart(&args).system_options.generate().is_err());
+        }
+        let kickstart =
+            parse_kickstart(&["--enable-service", "kdump", "--disable-service", "kdump"]);
+        assert!(kickstart.system_options.generate().is_err());
+        assert!(KickstartParser::try_parse_from([
+            "kickstart",
+            "--firewall-disabled",
+            "--firewall-service",
+            "ssh"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_kickstart_install_source() {
+        let profile = ReleaseProfile::default();
+        let (repos, source) = parse_kickstart(&[])
+            .install_source
+            .generate(&profile)
+            .unwrap();
+        assert_eq!(
+            repos,
+            r#"repo --name="AppStream" --baseurl=file:///run/install/sources/mount-0000-cdrom/AppStream"#
+        );
+        assert_eq!(source, "cdrom");
+
+        let kickstart = parse_kickstart(&[
+            "--install-url",
+            "http://mirror.example.com/almalinux/8/BaseOS/x86_64/os/",
+            "--install-proxy",
+            "http://proxy.example.com:3128",
+            "--repo",
+            "name=epel,mirrorlist=https://mirrors.fedoraproject.org/mirrorlist?repo=epel-8&arch=x86_64,cost=50,install",
+            "--repo",
+            "name=internal,baseurl=http://repo.example.com/el8/,proxy=http://proxy.example.com:3128",
+        ]);
+        let (repos, source) = kickstart.install_source.generate(&profile).unwrap();
+        assert_eq!(
+            repos,
+            r#"repo --name="AppStream" --baseurl=http://mirror.example.com/almalinux/8/AppStream/x86_64/os/
+repo --name="epel" --mirrorlist=https://mirrors.fedoraproject.org/mirrorlist?repo=epel-8&arch=x86_64 --cost=50 --install
+repo --name="internal" --baseurl=http://repo.example.com/el8/ --proxy=http://proxy.example.com:3128"#
+        );
+        assert_eq!(
+            source,
+            r#"url --url="http://mirror.example.com/almalinux/8/BaseOS/x86_64/os/" --proxy="http://proxy.example.com:3128""#
+        );
+
+        let mut kickstart = parse_kickstart(&["--install-nfs", "nfs.example.com:/srv/almalinux/8"]);
+        kickstart.use_install_location("http://mirror.example.com/almalinux/8/BaseOS/x86_64/os/");
+        let (repos, source) = kickstart.install_source.generate(&profile).unwrap();
+        assert_eq!(
+            repos,
+            r#"repo --name="AppStream" --baseurl=nfs://nfs.example.com:/srv/almalinux/8/AppStream"#
+        );
+        assert_eq!(
+            source,
+            "nfs --server=nfs.example.com --dir=/srv/almalinux/8"
+        );
+
+        for args in [
+            ["--install-nfs", "/srv/almalinux/8"],
+            ["--install-url", "mirror.example.com/almalinux"],
+            ["--repo", "name=epel,baseurl=http://a/,mirrorlist=http://b/"],
+            ["--repo", "name=AppStream,baseurl=http://a/"],
+        ] {
+            assert!(parse_kickstart(&args)
+                .install_source
+                .generate(&profile)
+                .is_err());
+        }
+        assert!("name=bad name,baseurl=http://a/".parse::<Repo>().is_err());
+    }
+
+    #[test]
+    fn test_kickstart_packages() {
+        let (options, packages) = parse_kickstart(&[]).system_options.packages(&[]).unwrap();
+        assert_eq!(options, "");
+        assert_eq!(packages, "@standard\n@guest-agents");
+
+        let kickstart = parse_kickstart(&[
+            "--add-packages",
+            "zsh,tmux",
+            "--add-packages",
+            "@development",
+            "--exclude-package",
+            "cockpit",
+            "--module-stream",
+            "postgresql:15",
+            "--module-stream",
+            "nodejs:18/minimal",
+            "--packages-nocore",
+            "--packages-excludedocs",
+            "--packages-ignoremissing",
+            "--packages-inst-langs",
+            "en_US,ja_JP",
+        ]);
+        let (options, packages) = kickstart
+            .system_options
+            .packages(&["policycoreutils-python-utils", "zsh"])
+            .unwrap();
+        assert_eq!(
+            options,
+            " --nocore --excludedocs --ignoremissing --instLangs=en_US,ja_JP"
+        );
+        assert_eq!(
+            packages,
+            r#"@standard
+@guest-agents
+zsh
+tmux
+@development
+@postgresql:15
+@nodejs:18/minimal
+policycoreutils-python-utils
+-cockpit"#
+        );
+
+        let (_, packages) = parse_kickstart(&["--packages", "vim,-cockpit", "--packages", "git"])
+            .system_options
+            .packages(&[])
+            .unwrap();
+        assert_eq!(packages, "vim\n-cockpit\ngit");
+
+        for args in [
+            ["--module-stream", "postgresql"],
+            ["--module-stream", "postgresql:15/"],
+            ["--packages", "@^server-product-environment"],
+            ["--packages", "-@postgresql:15"],
+            ["--packages", "vim; rm"],
+            ["--exclude-package", "-cockpit"],
+            ["--exclude-package", "@standard"],
+        ] {
+            assert!(parse_kickstart(&args).system_options.packages(&[]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_kickstart_kdump() {
+        let profile = ReleaseProfile::default();
+        let hardware = VmHardware {
+            memory: 1024,
+            disks: vec![],
+        };
+        assert_eq!(
+            parse_kickstart(&[])
+                .addons
+                .kdump(None, &profile)
+                .unwrap()
+                .generate(),
+            "%addon com_redhat_kdump --enable --reserve-mb='auto'\n\n%end"
+        );
+        assert_eq!(
+            parse_kickstart(&["--kdump-disabled"])
+                .addons
+                .kdump(Some(&hardware), &profile)
+                .unwrap()
+                .generate(),
+            "%addon com_redhat_kdump --disable\n\n%end"
+        );
+        let kickstart = parse_kickstart(&["--kdump-reserve-mb", "192"]);
+        assert_eq!(
+            kickstart
+                .addons
+                .kdump(Some(&hardware), &profile)
+                .unwrap()
+                .generate(),
+            "%addon com_redhat_kdump --enable --reserve-mb=192\n\n%end"
+        );
+        let kickstart = parse_kickstart(&["--kdump-reserve-mb", "768"]);
+        assert!(kickstart.addons.kdump(Some(&hardware), &profile).is_err());
+        let kickstart = parse_kickstart(&["--kdump-reserve-mb", "2147484000"]);
+        assert!(kickstart.addons.kdump(Some(&hardware), &profile).is_err());
+
+        let kickstart = parse_kickstart(&[
+            "--kdump-disabled",
+            "--addon",
+            "name=com_example_addon,line=key = value",
+        ]);
+        let addons: Vec<_> = kickstart
+            .addons
+            .generate(None, &profile)
+            .unwrap()
+            .iter()
+            .map(|x| x.generate())
+            .collect();
+        assert_eq!(
+            addons,
+            vec![
+                "%addon com_redhat_kdump --disable\n\n%end",
+                "%addon com_example_addon\nkey = value\n%end"
+            ]
+        );
+        let kickstart = parse_kickstart(&["--addon", "name=com_redhat_kdump,option=--disable"]);
+        assert!(kickstart.addons.generate(None, &profile).is_err());
+    }
+
+    #[test]
+    fn test_kickstart_oscap() {
+        let profile = ReleaseProfile::default();
+        let kickstart = parse_kickstart(&[
+            "--oscap-profile",
+            "xccdf_org.ssgproject.content_profile_cis",
+        ]);
+        assert_eq!(
+            kickstart
+                .addons
+                .oscap(&profile)
+                .unwrap()
+                .unwrap()
+                .generate(),
+            r#"%addon org_fedora_oscap
+    content-type = scap-security-guide
+    profile = xccdf_org.ssgproject.content_profile_cis
+%end"#
+        );
+        assert!(kickstart.initrd_files().is_empty());
+
+        let kickstart = parse_kickstart(&[
+            "--oscap-profile",
+            "xccdf_org.ssgproject.content_profile_stig_customized",
+            "--oscap-content-type",
+            "datastream",
+            "--oscap-content-url",
+            "http://scap.example.com/ssg-almalinux8-ds.xml",
+            "--oscap-datastream-id",
+            "scap_org.open-scap_datastream_from_xccdf_ssg-almalinux8-xccdf.xml",
+            "--oscap-xccdf-id",
+            "scap_org.open-scap_cref_ssg-almalinux8-xccdf.xml",
+            "--oscap-tailoring-file",
+            "testdata/tailoring.xml",
+        ]);
+        assert_eq!(
+            kickstart
+                .addons
+                .oscap(&profile)
+                .unwrap()
+                .unwrap()
+                .generate(),
+            r#"%addon org_fedora_oscap
+    content-type = datastream
+    content-url = http://scap.example.com/ssg-almalinux8-ds.xml
+    datastream-id = scap_org.open-scap_datastream_from_xccdf_ssg-almalinux8-xccdf.xml
+    xccdf-id = scap_org.open-scap_cref_ssg-almalinux8-xccdf.xml
+    tailoring-path = /tailoring.xml
+    profile = xccdf_org.ssgproject.content_profile_stig_customized
+%end"#
+        );
+        assert_eq!(kickstart.initrd_files(), vec!["testdata/tailoring.xml"]);
+
+        for args in [
+            vec!["--oscap-xccdf-id", "xccdf"],
+            vec![
+                "--oscap-profile",
+                "cis",
+                "--oscap-content-type",
+                "datastream",
+            ],
+            vec![
+                "--oscap-profile",
+                "cis",
+                "--oscap-content-url",
+                "http://a/ds.xml",
+            ],
+            vec!["--oscap-profile", "cis profile"],
+            vec![
+                "--oscap-profile",
+                "cis",
+                "--oscap-tailoring-file",
+                "testdata/none.xml",
+            ],
+        ] {
+            assert!(parse_kickstart(&args)
+                .addons
+                .generate(None, &profile)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_kickstart_time() {
+        assert_eq!(
+            parse_kickstart(&[]).system_options.time().unwrap(),
+            "timezone Asia/Tokyo --utc"
+        );
+        let args = [
+            "--ntp-server",
+            "ntp1.example.com,10.0.0.123",
+            "--timezone",
+            "UTC",
+        ];
+        assert_eq!(
+            parse_kickstart(&args).system_options.time().unwrap(),
+            "timezone UTC --utc --ntpservers=ntp1.example.com,10.0.0.123"
+        );
+        let kickstart = parse_kickstart(
+            &[
+                &args[..],
+                &["--os-version", "9", "--ntp-pool", "pool.example.com"],
+            ]
+            .concat(),
+        );
+        assert_eq!(
+            kickstart.system_options.time().unwrap(),
+            r#"timezone UTC --utc
+timesource --ntp-server=ntp1.example.com
+timesource --ntp-server=10.0.0.123
+timesource --ntp-pool=pool.example.com"#
+        );
+        assert_eq!(
+            parse_kickstart(&["--ntp-disabled"])
+                .system_options
+                .time()
+                .unwrap(),
+            "timezone Asia/Tokyo --utc --nontp"
+        );
+        assert_eq!(
+            parse_kickstart(&["--ntp-disabled", "--os-version", "9"])
+                .system_options
+                .time()
+                .unwrap(),
+            "timezone Asia/Tokyo --utc\ntimesource --ntp-disable"
+        );
+
+        assert!(parse_kickstart(&["--ntp-pool", "pool.example.com"])
+            .system_options
+            .time()
+            .is_err());
+        assert!(parse_kickstart(&["--ntp-server", "ntp_1.example.com"])
+            .system_options
+            .time()
+            .is_err());
+        assert!(parse_kickstart(&["--timezone", "../etc/passwd"])
+            .system_options
+            .time()
+            .is_err());
+        if std::path::Path::new(TZDATA).is_file() {
+            for timezone in [
+                "Asia/Nowhere",
+                "zone.tab",
+                "zone1970.tab",
+                "tzdata.zi",
+                "posixrules",
+            ] {
+                assert!(parse_kickstart(&["--timezone", timezone])
+                    .system_options
+                    .time()
+                    .is_err());
+            }
+            assert!(check_timezone("UTC").is_ok());
+        }
+        let names = timezone_names(
+            "# version 2024a\nR Japan 1948 o - May Sat>=1 24 1 D\nZ Asia/Tokyo 9:18:59 - LMT 1887 D 31 15u\nL Asia/Tokyo Japan\n",
+        );
+        assert_eq!(names, HashSet::from(["Asia/Tokyo", "Japan"]));
+    }
+
+    #[test]
+    fn test_kickstart_bootloader() {
+        assert_eq!(
+            parse_kickstart(&[]).bootloader.generate().unwrap(),
+            r#"bootloader --append="console=tty0 console=ttyS0,115200""#
+        );
+        let kickstart = parse_kickstart(&[
+            "--bootloader-append",
+            "crashkernel=256M",
+            "--bootloader-append",
+            "console=ttyS0,115200",
+            "--bootloader-location",
+            "mbr",
+            "--bootloader-timeout",
+            "3",
+            "--bootloader-password-crypt",
+            "grub.pbkdf2.sha512.10000.SALT.HASH",
+        ]);
+        assert_eq!(
+            kickstart.bootloader.generate().unwrap(),
+            r#"bootloader --append="console=tty0 console=ttyS0,115200 crashkernel=256M" --location=mbr --timeout=3 --iscrypted --password=grub.pbkdf2.sha512.10000.SALT.HASH"#
+        );
+        let kickstart = parse_kickstart(&[
+            "--no-serial-console",
+            "--bootloader-password-plain",
+            "pass word",
+        ]);
+        assert_eq!(
+            kickstart.bootloader.generate().unwrap(),
+            r#"bootloader --password="pass word""#
+        );
+
+        for args in [
+            ["--bootloader-append", "quiet rhgb"],
+            ["--bootloader-password-crypt", "$6$salt$hash"],
+        ] {
+            assert!(parse_kickstart(&args).bootloader.generate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_kickstart_completion_action() {
+        let generated = parse_kickstart(&[]).generate(None).unwrap();
+        assert_eq!(
+            generated
+                .lines()
+                .filter(|x| ["reboot", "poweroff", "shutdown", "halt"].contains(x))
+                .collect::<Vec<_>>(),
+            vec!["shutdown"]
+        );
+        assert!(!parse_kickstart(&[]).reboots_after_install());
+
+        let kickstart = parse_kickstart(&["--completion-action", "reboot"]);
+        let generated = kickstart.generate(None).unwrap();
+        assert_eq!(
+            generated
+                .lines()
+                .filter(|x| ["reboot", "poweroff", "shutdown", "halt"].contains(x))
+                .collect::<Vec<_>>(),
+            vec!["reboot"]
+        );
+        assert!(kickstart.reboots_after_install());
+    }
+
+    #[test]
+    fn test_kickstart_disk_initialization() {
+        let disks = vec!["vda".to_string(), "vdb".to_string()];
+        assert_eq!(
+            parse_kickstart(&[])
+                .storage
+                .initialization
+                .generate(&disks)
+                .unwrap(),
+            "clearpart --none --initlabel"
+        );
+        let kickstart = parse_kickstart(&[
+            "--clearpart",
+            "linux",
+            "--zerombr",
+            "--confirm-disk-initialization",
+        ]);
+        assert_eq!(
+            kickstart.storage.initialization.generate(&disks).unwrap(),
+            "zerombr\nclearpart --linux --initlabel"
+        );
+        let kickstart = parse_kickstart(&["--clearpart", "all", "--clearpart-drive", "vdb"]);
+        assert_eq!(
+            kickstart.storage.initialization.generate(&disks).unwrap(),
+            "clearpart --all --drives=vdb --initlabel"
+        );
+        let kickstart = parse_kickstart(&["--zerombr"]);
+        assert_eq!(
+            kickstart.storage.initialization.generate(&disks).unwrap(),
+            "zerombr\nclearpart --none --initlabel"
+        );
+
+        for args in [
+            vec!["--clearpart", "all"],
+            vec!["--clearpart", "linux", "--zerombr"],
+            vec!["--clearpart-drive", "vda"],
+            vec!["--clearpart", "linux", "--clearpart-drive", "sda"],
+        ] {
+            assert!(parse_kickstart(&args)
+                .storage
+                .initialization
+                .generate(&disks)
+                .is_err());
+        }
+        let kickstart = parse_kickstart(&["--clearpart", "all", "--confirm-disk-initialization"]);
+        assert_eq!(
+            kickstart.storage.initialization.generate(&disks).unwrap(),
+            "clearpart --all --initlabel"
+        );
+    }
+
+    #[test]
+    fn test_kickstart_profile() {
+        let generated = parse_kickstart(&[]).generate(None).unwrap();
+        assert!(generated.contains("\nvolgroup almalinux --pesize=4096 pv.116\n"));
+        assert!(generated.contains("\nrootpw --plaintext password\n"));
+
+        let kickstart = parse_kickstart(&[
+            "--distro",
+            "rocky",
+            "--os-version",
+            "9",
+            "--packages-inst-langs",
+            "en_US",
+        ]);
+        assert_eq!(kickstart.osinfo().unwrap(), "rocky9");
+        let generated = kickstart.generate(None).unwrap();
+        assert!(generated.contains("\nvolgroup rl --pesize=4096 pv.116\n"));
+        assert!(generated.contains("\nrootpw --plaintext password --allow-ssh\n"));
+        assert!(generated.contains("\n%addon com_redhat_kdump --enable\n"));
+        assert!(generated.contains("\n%packages --inst-langs=en_US\n"));
+        assert!(generated.contains("repo --name=\"AppStream\""));
+
+        let generated = parse_kickstart(&[
+            "--distro",
+            "rhel",
+            "--os-version",
+            "10",
+            "--sshd-permit-root-login",
+            "prohibit-password",
+            "--volume-group",
+            "data",
+        ])
+        .generate(None)
+        .unwrap();
+        assert!(generated.contains("\nrootpw --plaintext password\n"));
+        assert!(generated.contains("\nvolgroup data --pesize=4096 pv.116\n"));
+        let generated = parse_kickstart(&["--os-version", "9", "--sshd-permit-root-login", "yes"])
+            .generate(None)
+            .unwrap();
+        assert!(generated.contains("\nrootpw --plaintext password --allow-ssh\n"));
+
+        let kickstart = parse_kickstart(&["--distro", "fedora", "--os-version", "41"]);
+        assert_eq!(kickstart.osinfo().unwrap(), "fedora41");
+        let generated = kickstart.generate(None).unwrap();
+        assert!(!generated.contains("AppStream"));
+        assert!(generated.contains("\nvolgroup fedora --pesize=4096 pv.116\n"));
+
+        let profile = ReleaseProfile::new(Distro::Rhel, 9).unwrap();
+        let kickstart = parse_kickstart(&["--addon", "name=org_fedora_oscap,line=x"]);
+        assert!(kickstart.warnings(&ReleaseProfile::default()).is_empty());
+        assert_eq!(kickstart.warnings(&profile).len(), 1);
+        let generated = parse_kickstart(&[
+            "--distro",
+            "fedora",
+            "--os-version",
+            "41",
+            "--storage-scheme",
+            "btrfs",
+        ])
+        .generate(None)
+        .unwrap();
+        assert!(generated.contains("\nbtrfs / --subvol --name=root LABEL=root\n"));
+        let kickstart = parse_kickstart(&["--network-dns-search", "example.com"]);
+        assert_eq!(kickstart.warnings(&ReleaseProfile::default()).len(), 1);
+        assert!(kickstart.warnings(&profile).is_empty());
+
+        for args in [
+            vec!["--distro", "fedora"],
+            vec!["--os-version", "7"],
+            vec!["--storage-scheme", "btrfs"],
+            vec!["--os-version", "9", "--storage-scheme", "autopart-btrfs"],
+            vec![
+                "--distro",
+                "fedora",
+                "--os-version",
+                "41",
+                "--environment",
+                "graphical-server-environment",
+            ],
+        ] {
+            assert!(parse_kickstart(&args).generate(None).is_err());
+        }
+    }
 }
@@ -1,9 +1,16 @@
+mod addon;
 mod batch_install;
 mod createvm;
 mod kickstart;
 mod options_from_csv;
 mod passwd;
+mod profile;
 mod runall;
+mod scripts;
+mod spec;
+mod sshd;
+mod storage_layout;
+mod sudo;
 
 use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
@@ -1,5 +1,4 @@
 use anyhow::Context;
-use csv;
 use std::io::Read;
 
 pub fn generate_options_from_csv(reader: impl Read) -> anyhow::Result<Vec<Vec<String>>> {
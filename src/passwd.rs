@@ -29,12 +29,17 @@ pub fn read_passwd(prompt: &str) -> anyhow::Result<String> {
     Ok(password.unwrap_or_default())
 }
 
-pub fn read_and_encrypt_password(prompt: &str) -> anyhow::Result<String> {
+pub fn read_and_confirm_passwd(prompt: &str) -> anyhow::Result<String> {
     let password = read_passwd(prompt)?;
     let password2 = read_passwd("Confirm: ")?;
     if password != password2 {
         return Err(anyhow::anyhow!("Passwords do not match"));
     }
+    Ok(password)
+}
+
+pub fn read_and_encrypt_password(prompt: &str) -> anyhow::Result<String> {
+    let password = read_and_confirm_passwd(prompt)?;
 
     let params = sha_crypt::Sha512Params::new(5_000).expect("RandomError!");
     let encrypt_password = sha_crypt::sha512_simple(&password, &params).expect("Encrypt Error");
@@ -0,0 +1,235 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+#[derive(
+    ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Distro {
+    #[default]
+    Almalinux,
+    Rocky,
+    Rhel,
+    CentosStream,
+    Fedora,
+}
+
+const FEDORA_OLDEST: u32 = 34;
+
+/// Target distribution and release of the kickstart. The RHEL family uses the
+/// major version, Fedora its own release number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReleaseProfile {
+    distro: Distro,
+    version: u32,
+}
+
+impl Default for ReleaseProfile {
+    fn default() -> Self {
+        ReleaseProfile {
+            distro: Distro::Almalinux,
+            version: 8,
+        }
+    }
+}
+
+impl Display for ReleaseProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self.distro {
+            Distro::Almalinux => "AlmaLinux",
+            Distro::Rocky => "Rocky Linux",
+            Distro::Rhel => "RHEL",
+            Distro::CentosStream => "CentOS Stream",
+            Distro::Fedora => "Fedora",
+        };
+        write!(f, "{name} {}", self.version)
+    }
+}
+
+impl ReleaseProfile {
+    pub fn new(distro: Distro, version: u32) -> anyhow::Result<Self> {
+        match distro {
+            Distro::Fedora if version < FEDORA_OLDEST => Err(anyhow::anyhow!(
+                "Fedora {version} is not supported, give the Fedora release number (example: --os-version 41)"
+            )),
+            Distro::Fedora => Ok(ReleaseProfile { distro, version }),
+            _ if (8..=10).contains(&version) => Ok(ReleaseProfile { distro, version }),
+            _ => Err(anyhow::anyhow!(
+                "{} {version} is not supported, supported versions are 8, 9 and 10",
+                distro.to_possible_value().unwrap().get_name()
+            )),
+        }
+    }
+
+    pub fn is_fedora(&self) -> bool {
+        self.distro == Distro::Fedora
+    }
+
+    /// Whether the release is at least RHEL `el` or Fedora `fedora`.
+    fn since(&self, el: u32, fedora: u32) -> bool {
+        if self.is_fedora() {
+            self.version >= fedora
+        } else {
+            self.version >= el
+        }
+    }
+
+    /// Default `--osinfo` of virt-install.
+    pub fn osinfo(&self) -> String {
+        let version = self.version;
+        match self.distro {
+            Distro::Almalinux => format!("almalinux{version}"),
+            Distro::Rocky => format!("rocky{version}"),
+            Distro::Rhel => format!("rhel{version}-unknown"),
+            Distro::CentosStream => format!("centos-stream{version}"),
+            Distro::Fedora => format!("fedora{version}"),
+        }
+    }
+
+    /// Volume group name used by the installer for automatic partitioning.
+    pub fn volume_group(&self) -> &'static str {
+        match self.distro {
+            Distro::Almalinux => "almalinux",
+            Distro::Rocky => "rl",
+            Distro::Rhel => "rhel",
+            Distro::CentosStream => "cs",
+            Distro::Fedora => "fedora",
+        }
+    }
+
+    /// Fedora has no AppStream repository.
+    pub fn has_appstream(&self) -> bool {
+        !self.is_fedora()
+    }
+
+    /// `timesource` replaces `timezone --ntpservers` and `--nontp`.
+    pub fn has_timesource(&self) -> bool {
+        self.since(9, 33)
+    }
+
+    pub fn inst_langs_option(&self) -> &'static str {
+        if self.since(9, 34) {
+            "--inst-langs"
+        } else {
+            "--instLangs"
+        }
+    }
+
+    /// Root password SSH login has to be allowed with `rootpw --allow-ssh`, which
+    /// is available from Fedora 37 and RHEL 9.1.
+    pub fn root_ssh_disabled_by_default(&self) -> bool {
+        self.since(9, 37)
+    }
+
+    /// The kdump addon computes the reservation itself, `--reserve-mb='auto'`
+    /// is deprecated.
+    pub fn kdump_auto_deprecated(&self) -> bool {
+        self.since(9, 34)
+    }
+
+    pub fn oscap_addon(&self) -> &'static str {
+        if !self.is_fedora() && self.version >= 9 {
+            "com_redhat_oscap"
+        } else {
+            "org_fedora_oscap"
+        }
+    }
+
+    /// `network --ipv4-dns-search` needs the 8.7 installer on release 8, which the
+    /// profile cannot tell apart from older minor releases.
+    pub fn has_dns_search(&self) -> bool {
+        self.since(9, 36)
+    }
+
+    /// OpenSSH 8.7 renamed `ChallengeResponseAuthentication`.
+    pub fn kbd_interactive_auth(&self) -> bool {
+        self.since(9, 35)
+    }
+
+    pub fn has_environment(&self, environment: &str) -> bool {
+        !(self.is_fedora()
+            && matches!(
+                environment,
+                "graphical-server-environment" | "virtualization-host-environment"
+            ))
+    }
+
+    /// Warnings for a profile that is no longer maintained.
+    pub fn warnings(&self) -> Vec<String> {
+        if self.distro == Distro::CentosStream && self.version == 8 {
+            vec![format!(
+                "{self} reached its end of life, use version 9 or later"
+            )]
+        } else {
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_release_profile() {
+        let profile = ReleaseProfile::default();
+        assert_eq!(profile.osinfo(), "almalinux8");
+        assert_eq!(profile.volume_group(), "almalinux");
+        assert_eq!(profile.inst_langs_option(), "--instLangs");
+        assert_eq!(profile.oscap_addon(), "org_fedora_oscap");
+        assert!(profile.has_appstream());
+        assert!(!profile.has_timesource());
+
+        let profile = ReleaseProfile::new(Distro::Rhel, 9).unwrap();
+        assert_eq!(profile.osinfo(), "rhel9-unknown");
+        assert_eq!(profile.volume_group(), "rhel");
+        assert_eq!(profile.inst_langs_option(), "--inst-langs");
+        assert_eq!(profile.oscap_addon(), "com_redhat_oscap");
+        assert!(profile.root_ssh_disabled_by_default());
+        assert!(profile.has_dns_search());
+        assert!(!ReleaseProfile::default().has_dns_search());
+
+        let profile = ReleaseProfile::new(Distro::Rocky, 10).unwrap();
+        assert_eq!(profile.osinfo(), "rocky10");
+        assert_eq!(profile.volume_group(), "rl");
+        let profile = ReleaseProfile::new(Distro::CentosStream, 8).unwrap();
+        assert_eq!(profile.osinfo(), "centos-stream8");
+        assert_eq!(profile.to_string(), "CentOS Stream 8");
+        assert_eq!(profile.warnings().len(), 1);
+
+        let profile = ReleaseProfile::new(Distro::Fedora, 41).unwrap();
+        assert_eq!(profile.osinfo(), "fedora41");
+        assert_eq!(profile.oscap_addon(), "org_fedora_oscap");
+        assert!(!profile.has_appstream());
+        assert!(!profile.has_environment("graphical-server-environment"));
+
+        // (release, timesource, --inst-langs, --allow-ssh, no kdump auto, KbdInteractiveAuthentication)
+        for (version, expected) in [
+            (34, (true, true, false, true, false)),
+            (35, (true, true, false, true, true)),
+            (36, (true, true, false, true, true)),
+            (37, (true, true, true, true, true)),
+            (40, (true, true, true, true, true)),
+            (42, (true, true, true, true, true)),
+        ] {
+            let profile = ReleaseProfile::new(Distro::Fedora, version).unwrap();
+            assert_eq!(
+                (
+                    profile.has_timesource(),
+                    profile.inst_langs_option() == "--inst-langs",
+                    profile.root_ssh_disabled_by_default(),
+                    profile.kdump_auto_deprecated(),
+                    profile.kbd_interactive_auth(),
+                ),
+                expected,
+                "Fedora {version}"
+            );
+        }
+
+        assert!(ReleaseProfile::new(Distro::Almalinux, 7).is_err());
+        assert!(ReleaseProfile::new(Distro::Rhel, 11).is_err());
+        assert!(ReleaseProfile::new(Distro::Fedora, 9).is_err());
+        assert!(ReleaseProfile::new(Distro::Fedora, 33).is_err());
+    }
+}
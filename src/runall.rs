@@ -17,13 +17,86 @@ pub struct RunAll {
 }
 
 impl RunAll {
+    /// The kickstart installing from the `--location` of the VM.
+    fn kickstart(&self) -> anyhow::Result<kickstart::Kickstart> {
+        if self.kickstart.halts_after_install() {
+            return Err(anyhow::anyhow!(
+                "The halt completion action leaves the VM running after installation, use poweroff or shutdown"
+            ));
+        }
+        let mut kickstart = self.kickstart.clone();
+        if let Some(location) = self.create_vm.location() {
+            kickstart.use_install_location(location);
+        }
+        Ok(kickstart)
+    }
+
     pub fn run(&self) -> anyhow::Result<()> {
-        let kickstart = self.kickstart.generate()?;
+        let kickstart = self
+            .kickstart()?
+            .generate(Some(&self.create_vm.hardware()))?;
         let mut tmp = tempfile::NamedTempFile::new()?;
         tmp.write_all(kickstart.as_bytes())?;
         let kickstart_path = tmp.path().to_str().unwrap();
-        self.create_vm.create_vm(Some(kickstart_path))?;
+        let mut create_vm = self.create_vm.clone();
+        create_vm.match_network_interfaces(self.kickstart.network_interface_count())?;
+        create_vm.add_initrd_inject(self.kickstart.initrd_files());
+        create_vm.match_completion_action(self.kickstart.reboots_after_install());
+        create_vm.use_osinfo(self.kickstart.osinfo()?);
+        create_vm.create_vm(Some(kickstart_path))?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct RunAllParser {
+        #[command(flatten)]
+        run_all: RunAll,
+    }
+
+    fn parse_run_all(args: &[&str]) -> RunAll {
+        let base = ["run-all", "--vm-name", "vm1", "--rootpw-plain", "password"];
+        RunAllParser::parse_from(base.iter().chain(args.iter())).run_all
+    }
+
+    fn generate(location: &str) -> String {
+        let run_all = parse_run_all(&["--location", location]);
+        run_all
+            .kickstart()
+            .unwrap()
+            .generate(Some(&run_all.create_vm.hardware()))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_install_location() {
+        let generated = generate("http://mirror.example.com/almalinux/8/BaseOS/x86_64/os/");
+        assert!(generated
+            .contains("\nurl --url=\"http://mirror.example.com/almalinux/8/BaseOS/x86_64/os/\"\n"));
+        assert!(generated.contains(
+            "repo --name=\"AppStream\" --baseurl=http://mirror.example.com/almalinux/8/AppStream/x86_64/os/\n"
+        ));
+
+        let generated = generate("nfs:nfs.example.com:/srv/almalinux/8");
+        assert!(generated.contains("\nnfs --server=nfs.example.com --dir=/srv/almalinux/8\n"));
+        assert!(generated.contains(
+            "repo --name=\"AppStream\" --baseurl=nfs://nfs.example.com:/srv/almalinux/8/AppStream\n"
+        ));
+    }
+
+    #[test]
+    fn test_completion_action() {
+        for action in ["shutdown", "poweroff", "reboot"] {
+            let run_all = parse_run_all(&["--iso", "alma.iso", "--completion-action", action]);
+            assert!(run_all.kickstart().is_ok());
+        }
+        let run_all = parse_run_all(&["--iso", "alma.iso", "--completion-action", "halt"]);
+        assert!(run_all.kickstart().is_err());
+    }
+}
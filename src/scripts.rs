@@ -0,0 +1,305 @@
+use anyhow::Context;
+use base64::Engine;
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::spec;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct Script {
+    path: String,
+    #[serde(default)]
+    interpreter: Option<String>,
+    #[serde(default)]
+    nochroot: bool,
+    #[serde(default)]
+    log: Option<String>,
+}
+
+fn absolute_path(key: &str, value: Option<String>) -> anyhow::Result<String> {
+    let value = spec::required_value(key, value)?;
+    if !value.starts_with('/') {
+        return Err(anyhow::anyhow!("{key} must be an absolute path: {value}"));
+    }
+    Ok(value)
+}
+
+impl FromStr for Script {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut script = Script::default();
+        for (key, value) in spec::parse_spec(s)? {
+            match (key.as_str(), value) {
+                ("path", value) => script.path = spec::required_value(&key, value)?,
+                ("interpreter", value) => script.interpreter = Some(absolute_path(&key, value)?),
+                ("nochroot", value) => script.nochroot = spec::parse_bool(&key, value)?,
+                ("log", value) => script.log = Some(absolute_path(&key, value)?),
+                (_, None) if script.path.is_empty() => script.path = key,
+                _ => return Err(anyhow::anyhow!("Unknown script option: {key}")),
+            }
+        }
+        if script.path.is_empty() {
+            return Err(anyhow::anyhow!("Script path is not set"));
+        }
+        Ok(script)
+    }
+}
+
+impl Script {
+    fn generate(&self, section: &str) -> anyhow::Result<String> {
+        let body = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {section} script: {}", self.path))?;
+        if body.lines().any(|x| x.trim() == "%end") {
+            return Err(anyhow::anyhow!(
+                "{section} script must not contain %end: {}",
+                self.path
+            ));
+        }
+
+        let mut header = format!("%{section}");
+        if let Some(interpreter) = &self.interpreter {
+            header.push_str(&format!(" --interpreter={interpreter}"));
+        }
+        if self.nochroot {
+            header.push_str(" --nochroot");
+        }
+        if let Some(log) = &self.log {
+            header.push_str(&format!(" --log={log}"));
+        }
+        Ok(format!("{header}\n{}\n%end\n", body.trim_end()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct InjectedFile {
+    src: String,
+    dest: String,
+    #[serde(default)]
+    mode: Option<String>,
+    #[serde(default)]
+    owner: Option<String>,
+}
+
+impl FromStr for InjectedFile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut file = InjectedFile::default();
+        for (key, value) in spec::parse_spec(s)? {
+            match key.as_str() {
+                "src" => file.src = spec::required_value(&key, value)?,
+                "dest" => file.dest = absolute_path(&key, value)?,
+                "mode" => {
+                    let value = spec::required_value(&key, value)?;
+                    if !(3..=4).contains(&value.len()) || u32::from_str_radix(&value, 8).is_err() {
+                        return Err(anyhow::anyhow!("Invalid file mode: {value}"));
+                    }
+                    file.mode = Some(value);
+                }
+                "owner" => {
+                    let value = spec::required_value(&key, value)?;
+                    if value.is_empty()
+                        || !value
+                            .chars()
+                            .all(|x| x.is_ascii_alphanumeric() || "_.-:".contains(x))
+                    {
+                        return Err(anyhow::anyhow!("Invalid file owner: {value}"));
+                    }
+                    file.owner = Some(value);
+                }
+                _ => return Err(anyhow::anyhow!("Unknown file option: {key}")),
+            }
+        }
+        if file.src.is_empty() {
+            return Err(anyhow::anyhow!("File source path is not set"));
+        }
+        if file.dest.is_empty() {
+            return Err(anyhow::anyhow!("File destination path is not set"));
+        }
+        Ok(file)
+    }
+}
+
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r#"'\''"#))
+}
+
+/// Shell commands that write `content` to `dest` in a `%post` section.
+pub fn write_file(dest: &str, content: &[u8], mode: Option<&str>, owner: Option<&str>) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(content);
+    let quoted_dest = shell_quote(dest);
+
+    let mut lines = Vec::new();
+    if let Some(parent) = Path::new(dest).parent() {
+        lines.push(format!(
+            "mkdir -p {}",
+            shell_quote(&parent.to_string_lossy())
+        ));
+    }
+    lines.push(format!("base64 -d > {quoted_dest} <<'EOF_INJECTED_FILE'"));
+    for chunk in encoded.as_bytes().chunks(76) {
+        lines.push(String::from_utf8_lossy(chunk).to_string());
+    }
+    lines.push("EOF_INJECTED_FILE".to_string());
+    if let Some(owner) = owner {
+        lines.push(format!("chown {owner} {quoted_dest}"));
+    }
+    if let Some(mode) = mode {
+        lines.push(format!("chmod {mode} {quoted_dest}"));
+    }
+    lines.join("\n")
+}
+
+impl InjectedFile {
+    fn generate(&self) -> anyhow::Result<String> {
+        let content = std::fs::read(&self.src)
+            .with_context(|| format!("Failed to read injected file: {}", self.src))?;
+        Ok(write_file(
+            &self.dest,
+            &content,
+            self.mode.as_deref(),
+            self.owner.as_deref(),
+        ))
+    }
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize, Default)]
+pub struct KickstartScripts {
+    #[arg(
+        long,
+        help = "%pre script, can be repeated (example: path=pre.sh,interpreter=/usr/bin/python3,log=/tmp/ks-pre.log)"
+    )]
+    #[serde(default)]
+    pre_script: Vec<Script>,
+    #[arg(
+        long,
+        help = "%post script, can be repeated (example: path=post.sh,nochroot,log=/mnt/sysimage/root/ks-post.log)"
+    )]
+    #[serde(default)]
+    post_script: Vec<Script>,
+    #[arg(
+        long,
+        help = "File copied into the installed system, can be repeated (example: src=ops.sudoers,dest=/etc/sudoers.d/ops,mode=0440,owner=root:root)"
+    )]
+    #[serde(default)]
+    inject_file: Vec<InjectedFile>,
+}
+
+impl KickstartScripts {
+    /// `configuration` holds `%post` bodies generated from other options. They run
+    /// after the injected files and before the user supplied `%post` scripts, and
+    /// a failing body stops the installation.
+    pub fn generate(&self, configuration: &[String]) -> anyhow::Result<String> {
+        let mut sections = Vec::new();
+        for script in &self.pre_script {
+            if script.nochroot {
+                return Err(anyhow::anyhow!(
+                    "--nochroot is only valid for %post scripts: {}",
+                    script.path
+                ));
+            }
+            sections.push(script.generate("pre")?);
+        }
+        if !self.inject_file.is_empty() {
+            let mut files = Vec::new();
+            for file in &self.inject_file {
+                files.push(file.generate()?);
+            }
+            sections.push(format!("%post\n{}\n%end\n", files.join("\n")));
+        }
+        for body in configuration {
+            sections.push(format!("%post --erroronfail\n{body}\n%end\n"));
+        }
+        for script in &self.post_script {
+            sections.push(script.generate("post")?);
+        }
+        Ok(sections.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scripts() {
+        let scripts = KickstartScripts {
+            pre_script: vec!["testdata/pre.sh".parse().unwrap()],
+            post_script: vec![
+                "path=testdata/post.sh,log=/root/ks-post.log"
+                    .parse()
+                    .unwrap(),
+                "path=testdata/pre.sh,interpreter=/bin/sh,nochroot"
+                    .parse()
+                    .unwrap(),
+            ],
+            inject_file: vec![],
+        };
+        let generated = scripts.generate(&[]).unwrap();
+        assert!(generated.starts_with("%pre\n#!/bin/sh\n"));
+        let post = generated.find("%post --log=/root/ks-post.log\n").unwrap();
+        let nochroot = generated
+            .find("%post --interpreter=/bin/sh --nochroot\n")
+            .unwrap();
+        assert!(post < nochroot);
+        assert_eq!(generated.matches("%end\n").count(), 3);
+        let generated = scripts.generate(&["echo configured".to_string()]).unwrap();
+        assert!(generated
+            .contains("%end\n\n%post --erroronfail\necho configured\n%end\n\n%post --log="));
+
+        assert!("path=post.sh,interpreter=python3"
+            .parse::<Script>()
+            .is_err());
+        assert!("log=/tmp/x.log".parse::<Script>().is_err());
+        let scripts = KickstartScripts {
+            pre_script: vec!["testdata/pre.sh,nochroot".parse().unwrap()],
+            ..Default::default()
+        };
+        assert!(scripts.generate(&[]).is_err());
+    }
+
+    #[test]
+    fn test_inject_file() {
+        let scripts = KickstartScripts {
+            post_script: vec!["testdata/post.sh".parse().unwrap()],
+            inject_file: vec![
+                "src=testdata/id_rsa.pub,dest=/etc/ssh/ca's key.pub,mode=0644,owner=root:root"
+                    .parse()
+                    .unwrap(),
+            ],
+            ..Default::default()
+        };
+        let generated = scripts.generate(&[]).unwrap();
+        let files = generated
+            .find("base64 -d > '/etc/ssh/ca'\\''s key.pub'")
+            .unwrap();
+        assert!(files < generated.find("#!/bin/bash").unwrap());
+        assert!(generated.contains("mkdir -p '/etc/ssh'\n"));
+        assert!(generated.contains("chown root:root '/etc/ssh/ca'\\''s key.pub'\n"));
+        assert!(generated.contains("chmod 0644 '/etc/ssh/ca'\\''s key.pub'\n"));
+
+        let encoded: String = generated
+            .lines()
+            .skip_while(|x| !x.starts_with("base64 -d"))
+            .skip(1)
+            .take_while(|x| *x != "EOF_INJECTED_FILE")
+            .collect();
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .unwrap(),
+            std::fs::read("testdata/id_rsa.pub").unwrap()
+        );
+
+        assert!("src=a,dest=etc/a".parse::<InjectedFile>().is_err());
+        assert!("src=a,dest=/etc/a,mode=0999"
+            .parse::<InjectedFile>()
+            .is_err());
+        assert!("src=a,dest=/etc/a,owner=root;rm"
+            .parse::<InjectedFile>()
+            .is_err());
+    }
+}
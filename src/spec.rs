@@ -0,0 +1,68 @@
+/// Parse a comma separated `key=value` list such as `device=enp2s0,bootproto=dhcp`.
+///
+/// Values may be wrapped in double quotes to include commas. Items without `=`
+/// are returned with `None` as their value.
+pub fn parse_spec(spec: &str) -> anyhow::Result<Vec<(String, Option<String>)>> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut in_quote = false;
+    for c in spec.chars() {
+        match c {
+            '"' => in_quote = !in_quote,
+            ',' if !in_quote => {
+                items.push(std::mem::take(&mut current));
+            }
+            _ => current.push(c),
+        }
+    }
+    if in_quote {
+        return Err(anyhow::anyhow!("Unterminated quote in \"{spec}\""));
+    }
+    items.push(current);
+
+    let mut result = Vec::new();
+    for item in items {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+        if let Some((key, value)) = item.split_once('=') {
+            result.push((key.trim().to_string(), Some(value.to_string())));
+        } else {
+            result.push((item.to_string(), None));
+        }
+    }
+    Ok(result)
+}
+
+pub fn required_value(key: &str, value: Option<String>) -> anyhow::Result<String> {
+    value.ok_or_else(|| anyhow::anyhow!("\"{key}\" requires a value"))
+}
+
+pub fn parse_bool(key: &str, value: Option<String>) -> anyhow::Result<bool> {
+    match value.as_deref() {
+        None | Some("yes") | Some("true") | Some("on") => Ok(true),
+        Some("no") | Some("false") | Some("off") => Ok(false),
+        Some(other) => Err(anyhow::anyhow!(
+            "Invalid value for \"{key}\": {other} (expected yes or no)"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_spec() {
+        assert_eq!(
+            parse_spec(r#"device=enp2s0,activate,comment="a,b""#).unwrap(),
+            vec![
+                ("device".to_string(), Some("enp2s0".to_string())),
+                ("activate".to_string(), None),
+                ("comment".to_string(), Some("a,b".to_string())),
+            ]
+        );
+        assert!(parse_spec(r#"device="enp2s0"#).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use crate::profile::ReleaseProfile;
+use crate::scripts::write_file;
+
+const SSHD_DROPIN: &str = "/etc/ssh/sshd_config.d/10-unattended-kvm-install.conf";
+
+#[derive(ValueEnum, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermitRootLogin {
+    Yes,
+    No,
+    ProhibitPassword,
+}
+
+impl PermitRootLogin {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PermitRootLogin::Yes => "yes",
+            PermitRootLogin::No => "no",
+            PermitRootLogin::ProhibitPassword => "prohibit-password",
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SshdConfig {
+    #[arg(long, help = "Disable SSH password authentication")]
+    #[serde(default)]
+    sshd_disable_password_auth: bool,
+    #[arg(long, help = "SSH root login policy")]
+    #[serde(default)]
+    sshd_permit_root_login: Option<PermitRootLogin>,
+    #[arg(long, help = "SSH server port")]
+    #[serde(default)]
+    sshd_port: Option<u16>,
+    #[arg(long, help = "User allowed to log in with SSH, can be repeated")]
+    #[serde(default)]
+    sshd_allow_users: Vec<String>,
+}
+
+impl SshdConfig {
+    pub fn password_auth_disabled(&self) -> bool {
+        self.sshd_disable_password_auth
+    }
+
+    pub fn root_login_disabled(&self) -> bool {
+        self.sshd_permit_root_login == Some(PermitRootLogin::No)
+    }
+
+    /// Root may log in with a password unless another policy is given.
+    pub fn root_login_allowed(&self) -> bool {
+        matches!(
+            self.sshd_permit_root_login,
+            None | Some(PermitRootLogin::Yes)
+        )
+    }
+
+    /// Packages needed by the `%post` section.
+    pub fn packages(&self) -> Vec<&'static str> {
+        match self.sshd_port {
+            Some(port) if port != 22 => vec!["policycoreutils-python-utils"],
+            _ => vec![],
+        }
+    }
+
+    fn sshd_config(&self, profile: &ReleaseProfile) -> anyhow::Result<Option<String>> {
+        let mut lines = vec!["# Generated by unattended-kvm-install".to_string()];
+        if self.sshd_disable_password_auth {
+            lines.push("PasswordAuthentication no".to_string());
+            if profile.kbd_interactive_auth() {
+                lines.push("KbdInteractiveAuthentication no".to_string());
+            } else {
+                lines.push("ChallengeResponseAuthentication no".to_string());
+            }
+        }
+        if let Some(permit_root_login) = self.sshd_permit_root_login {
+            lines.push(format!("PermitRootLogin {}", permit_root_login.as_str()));
+        }
+        if let Some(port) = self.sshd_port {
+            if port == 0 {
+                return Err(anyhow::anyhow!("Invalid SSH port: {port}"));
+            }
+            lines.push(format!("Port {port}"));
+        }
+        if !self.sshd_allow_users.is_empty() {
+            for user in &self.sshd_allow_users {
+                if user.is_empty()
+                    || !user
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "_.-@*?".contains(c))
+                {
+                    return Err(anyhow::anyhow!("Invalid SSH allowed user: {user}"));
+                }
+            }
+            lines.push(format!("AllowUsers {}", self.sshd_allow_users.join(" ")));
+        }
+        if lines.len() == 1 {
+            return Ok(None);
+        }
+        Ok(Some(lines.join("\n") + "\n"))
+    }
+
+    /// `%post` body installing the sshd drop-in, or `None` without sshd options.
+    pub fn generate(&self, profile: &ReleaseProfile) -> anyhow::Result<Option<String>> {
+        let Some(sshd_config) = self.sshd_config(profile)? else {
+            return Ok(None);
+        };
+        let mut lines = vec![
+            // AlmaLinux 8 does not include sshd_config.d by default
+            "grep -q '^Include /etc/ssh/sshd_config.d/' /etc/ssh/sshd_config || sed -i '1i Include /etc/ssh/sshd_config.d/*.conf' /etc/ssh/sshd_config".to_string(),
+            write_file(
+                SSHD_DROPIN,
+                sshd_config.as_bytes(),
+                Some("0600"),
+                Some("root:root"),
+            ),
+            // Host keys are only generated on first boot, so the configuration is
+            // checked with a throwaway key
+            "hostkey_dir=$(mktemp -d)".to_string(),
+            "ssh-keygen -q -t ed25519 -N '' -f \"$hostkey_dir/hostkey\" || exit 1".to_string(),
+            // The section runs with --erroronfail, so an invalid drop-in stops the
+            // installation instead of leaving the distribution defaults
+            format!("if ! sshd -t -h \"$hostkey_dir/hostkey\"; then\n    echo \"Invalid sshd configuration in {SSHD_DROPIN}\" >&2\n    rm -rf \"$hostkey_dir\"\n    exit 1\nfi"),
+            "rm -rf \"$hostkey_dir\"".to_string(),
+        ];
+        if let Some(port) = self.sshd_port.filter(|x| *x != 22) {
+            lines.push(format!(
+                "semanage port -a -t ssh_port_t -p tcp {port} || semanage port -m -t ssh_port_t -p tcp {port}"
+            ));
+            lines.push(format!(
+                "firewall-offline-cmd --add-port={port}/tcp || true"
+            ));
+        }
+        Ok(Some(lines.join("\n")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::profile::Distro;
+
+    #[test]
+    fn test_sshd_config() {
+        let profile = ReleaseProfile::default();
+        let sshd = SshdConfig {
+            sshd_disable_password_auth: true,
+            sshd_permit_root_login: Some(PermitRootLogin::No),
+            sshd_port: Some(2222),
+            sshd_allow_users: vec!["ops".to_string(), "deploy@10.0.0.*".to_string()],
+        };
+        assert_eq!(
+            sshd.sshd_config(&profile).unwrap().unwrap(),
+            r#"# Generated by unattended-kvm-install
+PasswordAuthentication no
+ChallengeResponseAuthentication no
+PermitRootLogin no
+Port 2222
+AllowUsers ops deploy@10.0.0.*
+"#
+        );
+        let generated = sshd.generate(&profile).unwrap().unwrap();
+        assert!(generated.starts_with("grep -q '^Include /etc/ssh/sshd_config.d/'"));
+        assert!(generated
+            .contains("chmod 0600 '/etc/ssh/sshd_config.d/10-unattended-kvm-install.conf'\n"));
+        assert!(generated.contains("semanage port -a -t ssh_port_t -p tcp 2222"));
+        assert!(generated
+            .contains("ssh-keygen -q -t ed25519 -N '' -f \"$hostkey_dir/hostkey\" || exit 1\n"));
+        assert!(generated.contains("if ! sshd -t -h \"$hostkey_dir/hostkey\"; then\n"));
+        assert!(!generated.contains("sshd -t;"));
+        assert!(generated.contains("\n    exit 1\nfi\n"));
+        assert!(!generated.contains("rm -f '/etc/ssh/sshd_config.d/"));
+        assert_eq!(sshd.packages(), vec!["policycoreutils-python-utils"]);
+        assert_eq!(SshdConfig::default().generate(&profile).unwrap(), None);
+
+        let mut invalid = sshd.clone();
+        invalid.sshd_allow_users.push("ops deploy".to_string());
+        assert!(invalid.generate(&profile).is_err());
+        let mut invalid = sshd.clone();
+        invalid.sshd_port = Some(0);
+        assert!(invalid.generate(&profile).is_err());
+
+        let profile = ReleaseProfile::new(Distro::Rocky, 9).unwrap();
+        let generated = sshd.sshd_config(&profile).unwrap().unwrap();
+        assert!(generated.contains("\nKbdInteractiveAuthentication no\n"));
+        assert!(!generated.contains("ChallengeResponseAuthentication"));
+    }
+}
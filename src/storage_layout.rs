@@ -0,0 +1,1040 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Partition {
+    pub mount: String,
+    #[serde(default)]
+    pub fstype: Option<String>,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub recommended: bool,
+    #[serde(default)]
+    pub grow: bool,
+    #[serde(default)]
+    pub maxsize: Option<u64>,
+    #[serde(default)]
+    pub ondisk: Option<String>,
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LogicalVolume {
+    pub mount: String,
+    pub name: String,
+    #[serde(default)]
+    pub fstype: Option<String>,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub recommended: bool,
+    #[serde(default)]
+    pub grow: bool,
+    #[serde(default)]
+    pub maxsize: Option<u64>,
+    #[serde(default)]
+    pub thinpool: bool,
+    #[serde(default)]
+    pub poolname: Option<String>,
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeGroup {
+    pub name: String,
+    #[serde(default)]
+    pub pesize: Option<u64>,
+    pub physical_volumes: Vec<String>,
+    #[serde(default)]
+    pub logical_volumes: Vec<LogicalVolume>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RaidDevice {
+    pub mount: String,
+    pub device: String,
+    pub level: u8,
+    #[serde(default)]
+    pub fstype: Option<String>,
+    pub members: Vec<String>,
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+impl RaidDevice {
+    fn minimum_members(&self) -> Option<usize> {
+        match self.level {
+            0 | 1 => Some(2),
+            4 | 5 => Some(3),
+            6 | 10 => Some(4),
+            _ => None,
+        }
+    }
+
+    fn data_members(&self) -> u64 {
+        let members = self.members.len() as u64;
+        match self.level {
+            0 => members,
+            4 | 5 => members - 1,
+            6 => members - 2,
+            10 => members / 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct BtrfsSubvolume {
+    pub mount: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct BtrfsVolume {
+    pub label: String,
+    pub members: Vec<String>,
+    #[serde(default)]
+    pub subvolumes: Vec<BtrfsSubvolume>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct StorageLayout {
+    #[serde(default)]
+    pub reqpart: bool,
+    #[serde(default)]
+    pub partitions: Vec<Partition>,
+    #[serde(default)]
+    pub raids: Vec<RaidDevice>,
+    #[serde(default)]
+    pub volume_groups: Vec<VolumeGroup>,
+    #[serde(default)]
+    pub btrfs_volumes: Vec<BtrfsVolume>,
+}
+
+impl Partition {
+    fn new(mount: &str, size: u64, grow: bool) -> Self {
+        Partition {
+            mount: mount.to_string(),
+            fstype: None,
+            size,
+            recommended: false,
+            grow,
+            maxsize: None,
+            ondisk: None,
+            encrypted: false,
+        }
+    }
+}
+
+impl LogicalVolume {
+    fn new(mount: &str, name: &str, size: u64, grow: bool) -> Self {
+        LogicalVolume {
+            mount: mount.to_string(),
+            name: name.to_string(),
+            fstype: None,
+            size,
+            recommended: false,
+            grow,
+            maxsize: None,
+            thinpool: false,
+            poolname: None,
+            encrypted: false,
+        }
+    }
+}
+
+impl Default for StorageLayout {
+    fn default() -> Self {
+        StorageLayout {
+            reqpart: true,
+            partitions: vec![
+                Partition::new("pv.116", 15360, true),
+                Partition::new("/boot", 1024, false),
+            ],
+            raids: vec![],
+            volume_groups: vec![VolumeGroup {
+                name: "almalinux".to_string(),
+                pesize: Some(4096),
+                physical_volumes: vec!["pv.116".to_string()],
+                logical_volumes: vec![
+                    LogicalVolume::new("swap", "swap", 4030, false),
+                    LogicalVolume::new("/", "root", 10240, true),
+                ],
+            }],
+            btrfs_volumes: vec![],
+        }
+    }
+}
+
+impl StorageLayout {
+    pub fn thin_lvm() -> Self {
+        let mut layout = StorageLayout::default();
+        let pool = LogicalVolume {
+            thinpool: true,
+            ..LogicalVolume::new("none", "pool00", 10240, true)
+        };
+        let root = LogicalVolume {
+            poolname: Some("pool00".to_string()),
+            ..LogicalVolume::new("/", "root", 10240, false)
+        };
+        layout.volume_groups[0].logical_volumes =
+            vec![LogicalVolume::new("swap", "swap", 4030, false), pool, root];
+        layout
+    }
+
+    pub fn plain() -> Self {
+        StorageLayout {
+            reqpart: true,
+            partitions: vec![
+                Partition::new("/boot", 1024, false),
+                Partition::new("swap", 4030, false),
+                Partition::new("/", 10240, true),
+            ],
+            raids: vec![],
+            volume_groups: vec![],
+            btrfs_volumes: vec![],
+        }
+    }
+
+    pub fn raid1(first_disk: &str, second_disk: &str) -> Self {
+        let on = |partition: Partition, disk: &str| Partition {
+            ondisk: Some(disk.to_string()),
+            ..partition
+        };
+        StorageLayout {
+            partitions: vec![
+                on(Partition::new("raid.01", 1024, false), first_disk),
+                on(Partition::new("raid.02", 1024, false), second_disk),
+                on(Partition::new("raid.11", 15360, true), first_disk),
+                on(Partition::new("raid.12", 15360, true), second_disk),
+            ],
+            raids: vec![
+                RaidDevice {
+                    mount: "/boot".to_string(),
+                    device: "boot".to_string(),
+                    level: 1,
+                    fstype: None,
+                    members: vec!["raid.01".to_string(), "raid.02".to_string()],
+                    encrypted: false,
+                },
+                RaidDevice {
+                    mount: "pv.116".to_string(),
+                    device: "pv00".to_string(),
+                    level: 1,
+                    fstype: None,
+                    members: vec!["raid.11".to_string(), "raid.12".to_string()],
+                    encrypted: false,
+                },
+            ],
+            ..StorageLayout::default()
+        }
+    }
+
+    pub fn btrfs() -> Self {
+        let boot = Partition {
+            fstype: Some("ext4".to_string()),
+            ..Partition::new("/boot", 1024, false)
+        };
+        StorageLayout {
+            reqpart: true,
+            partitions: vec![
+                boot,
+                Partition::new("swap", 4030, false),
+                Partition::new("btrfs.01", 10240, true),
+            ],
+            raids: vec![],
+            volume_groups: vec![],
+            btrfs_volumes: vec![BtrfsVolume {
+                label: "root".to_string(),
+                members: vec!["btrfs.01".to_string()],
+                subvolumes: vec![
+                    BtrfsSubvolume {
+                        mount: "/".to_string(),
+                        name: "root".to_string(),
+                    },
+                    BtrfsSubvolume {
+                        mount: "/home".to_string(),
+                        name: "home".to_string(),
+                    },
+                ],
+            }],
+        }
+    }
+}
+
+fn default_fstype<'a>(mount: &str, filesystem: &'a str) -> &'a str {
+    if mount.starts_with("pv.") {
+        "lvmpv"
+    } else if mount.starts_with("btrfs.") {
+        "btrfs"
+    } else if mount.starts_with("raid.") {
+        "mdmember"
+    } else if mount == "swap" {
+        "swap"
+    } else {
+        filesystem
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapSize {
+    None,
+    Recommended,
+    Fixed(u64),
+}
+
+const REQPART_SIZE: u64 = 600;
+
+pub fn recommended_swap_size(memory: u64) -> u64 {
+    if memory <= 2048 {
+        memory * 2
+    } else if memory <= 8192 {
+        memory
+    } else {
+        4096
+    }
+}
+
+fn size_options(size: u64, recommended: bool, grow: bool, maxsize: Option<u64>) -> String {
+    let mut options = if recommended {
+        " --recommended".to_string()
+    } else {
+        format!(" --size={size}")
+    };
+    if grow {
+        options.push_str(" --grow");
+    }
+    if let Some(maxsize) = maxsize {
+        options.push_str(&format!(" --maxsize={maxsize}"));
+    }
+    options
+}
+
+impl StorageLayout {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let reader = BufReader::new(
+            File::open(path).with_context(|| format!("Failed to open storage layout {path}"))?,
+        );
+        serde_json::from_reader(reader)
+            .with_context(|| format!("Failed to parse storage layout {path}"))
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut mounts = HashSet::new();
+        let mut check_mount = |mount: &str| {
+            if mount != "swap" && mount != "none" && !mounts.insert(mount.to_string()) {
+                return Err(anyhow::anyhow!("{mount} is defined more than once"));
+            }
+            Ok(())
+        };
+        let mut physical_volumes = HashSet::new();
+        let mut btrfs_members = HashSet::new();
+        let mut raid_members = HashSet::new();
+        for partition in &self.partitions {
+            check_mount(&partition.mount)?;
+            if partition.mount.starts_with("pv.") {
+                physical_volumes.insert(partition.mount.as_str());
+            } else if partition.mount.starts_with("btrfs.") {
+                btrfs_members.insert(partition.mount.as_str());
+            } else if partition.mount.starts_with("raid.") {
+                raid_members.insert(partition.mount.as_str());
+            } else if !partition.mount.starts_with('/') && partition.mount != "swap" {
+                return Err(anyhow::anyhow!(
+                    "Invalid partition mount point: {}",
+                    partition.mount
+                ));
+            }
+            if partition.size == 0 && !partition.recommended {
+                return Err(anyhow::anyhow!("Size of {} is zero", partition.mount));
+            }
+        }
+        let mut used_raid_members = HashSet::new();
+        let mut raid_devices = HashSet::new();
+        for raid in &self.raids {
+            check_mount(&raid.mount)?;
+            if raid.mount.starts_with("pv.") {
+                physical_volumes.insert(raid.mount.as_str());
+            } else if !raid.mount.starts_with('/') && raid.mount != "swap" {
+                return Err(anyhow::anyhow!("Invalid RAID mount point: {}", raid.mount));
+            }
+            if raid.device.is_empty()
+                || !raid
+                    .device
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                return Err(anyhow::anyhow!("Invalid RAID device name: {}", raid.device));
+            }
+            if !raid_devices.insert(raid.device.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "RAID device {} is defined more than once",
+                    raid.device
+                ));
+            }
+            let minimum = raid.minimum_members().ok_or_else(|| {
+                anyhow::anyhow!("Unsupported RAID level of {}: {}", raid.device, raid.level)
+            })?;
+            if raid.members.len() < minimum {
+                return Err(anyhow::anyhow!(
+                    "RAID{} device {} needs at least {minimum} members",
+                    raid.level,
+                    raid.device
+                ));
+            }
+            for member in &raid.members {
+                if !raid_members.contains(member.as_str()) {
+                    return Err(anyhow::anyhow!(
+                        "RAID member {member} of {} is not defined in partitions",
+                        raid.device
+                    ));
+                }
+                if !used_raid_members.insert(member.as_str()) {
+                    return Err(anyhow::anyhow!(
+                        "RAID member {member} is used by more than one device"
+                    ));
+                }
+            }
+        }
+        if let Some(member) = raid_members
+            .iter()
+            .find(|x| !used_raid_members.contains(*x))
+        {
+            return Err(anyhow::anyhow!(
+                "RAID member {member} is not used by any device"
+            ));
+        }
+        let mut used_physical_volumes = HashSet::new();
+        for volume_group in &self.volume_groups {
+            if volume_group.physical_volumes.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Volume group {} has no physical volumes",
+                    volume_group.name
+                ));
+            }
+            for pv in &volume_group.physical_volumes {
+                if !physical_volumes.contains(pv.as_str()) {
+                    return Err(anyhow::anyhow!(
+                        "Physical volume {pv} of volume group {} is not defined in partitions",
+                        volume_group.name
+                    ));
+                }
+                if !used_physical_volumes.insert(pv.as_str()) {
+                    return Err(anyhow::anyhow!(
+                        "Physical volume {pv} is used by more than one volume group"
+                    ));
+                }
+            }
+            let mut names = HashSet::new();
+            let thinpools: HashSet<&str> = volume_group
+                .logical_volumes
+                .iter()
+                .filter(|x| x.thinpool)
+                .map(|x| x.name.as_str())
+                .collect();
+            for logical_volume in &volume_group.logical_volumes {
+                if logical_volume.thinpool {
+                    if logical_volume.mount != "none" || logical_volume.poolname.is_some() {
+                        return Err(anyhow::anyhow!(
+                            "Thin pool {} must be mounted on \"none\" and cannot be in another pool",
+                            logical_volume.name
+                        ));
+                    }
+                } else if logical_volume.mount == "none" {
+                    return Err(anyhow::anyhow!(
+                        "Logical volume {} has no mount point",
+                        logical_volume.name
+                    ));
+                }
+                if let Some(poolname) = &logical_volume.poolname {
+                    if !thinpools.contains(poolname.as_str()) {
+                        return Err(anyhow::anyhow!(
+                            "Thin pool {poolname} of {} is not defined in {}",
+                            logical_volume.name,
+                            volume_group.name
+                        ));
+                    }
+                }
+                check_mount(&logical_volume.mount)?;
+                if !names.insert(logical_volume.name.as_str()) {
+                    return Err(anyhow::anyhow!(
+                        "Logical volume {} is defined more than once in {}",
+                        logical_volume.name,
+                        volume_group.name
+                    ));
+                }
+                if logical_volume.size == 0 && !logical_volume.recommended {
+                    return Err(anyhow::anyhow!("Size of {} is zero", logical_volume.mount));
+                }
+            }
+        }
+        if let Some(pv) = physical_volumes
+            .iter()
+            .find(|x| !used_physical_volumes.contains(*x))
+        {
+            return Err(anyhow::anyhow!(
+                "Physical volume {pv} is not used by any volume group"
+            ));
+        }
+        let mut used_btrfs_members = HashSet::new();
+        for volume in &self.btrfs_volumes {
+            if volume.members.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Btrfs volume {} has no members",
+                    volume.label
+                ));
+            }
+            for member in &volume.members {
+                if !btrfs_members.contains(member.as_str()) {
+                    return Err(anyhow::anyhow!(
+                        "Btrfs member {member} of {} is not defined in partitions",
+                        volume.label
+                    ));
+                }
+                if !used_btrfs_members.insert(member.as_str()) {
+                    return Err(anyhow::anyhow!(
+                        "Btrfs member {member} is used by more than one volume"
+                    ));
+                }
+            }
+            for subvolume in &volume.subvolumes {
+                check_mount(&subvolume.mount)?;
+            }
+        }
+        if let Some(member) = btrfs_members
+            .iter()
+            .find(|x| !used_btrfs_members.contains(*x))
+        {
+            return Err(anyhow::anyhow!(
+                "Btrfs member {member} is not used by any volume"
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn apply_swap(&mut self, swap: SwapSize) -> anyhow::Result<()> {
+        let mut found = false;
+        self.partitions.retain_mut(|partition| {
+            if partition.mount != "swap" {
+                return true;
+            }
+            found = true;
+            match swap {
+                SwapSize::None => return false,
+                SwapSize::Recommended => partition.recommended = true,
+                SwapSize::Fixed(size) => {
+                    partition.size = size;
+                    partition.recommended = false;
+                }
+            }
+            partition.grow = false;
+            true
+        });
+        for volume_group in &mut self.volume_groups {
+            volume_group.logical_volumes.retain_mut(|logical_volume| {
+                if logical_volume.mount != "swap" {
+                    return true;
+                }
+                found = true;
+                match swap {
+                    SwapSize::None => return false,
+                    SwapSize::Recommended => logical_volume.recommended = true,
+                    SwapSize::Fixed(size) => {
+                        logical_volume.size = size;
+                        logical_volume.recommended = false;
+                    }
+                }
+                logical_volume.grow = false;
+                true
+            });
+        }
+        if !found && swap != SwapSize::None {
+            return Err(anyhow::anyhow!("Storage layout has no swap volume"));
+        }
+        Ok(())
+    }
+
+    /// Renames the volume groups of a preset layout, which have a single one.
+    pub fn rename_volume_group(&mut self, name: &str) -> anyhow::Result<()> {
+        if name.is_empty()
+            || name.starts_with('-')
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_.+-".contains(c))
+        {
+            return Err(anyhow::anyhow!("Invalid volume group name: {name}"));
+        }
+        for volume_group in &mut self.volume_groups {
+            volume_group.name = name.to_string();
+        }
+        Ok(())
+    }
+
+    fn partition(&self, mount: &str) -> Option<&Partition> {
+        self.partitions.iter().find(|x| x.mount == mount)
+    }
+
+    fn disk_of<'a>(&'a self, partition: &'a Partition, default_disk: &'a str) -> &'a str {
+        partition.ondisk.as_deref().unwrap_or(default_disk)
+    }
+
+    pub fn disks<'a>(&'a self, default_disk: &'a str) -> Vec<&'a str> {
+        let mut disks = vec![];
+        for partition in &self.partitions {
+            let disk = self.disk_of(partition, default_disk);
+            if !disks.contains(&disk) {
+                disks.push(disk);
+            }
+        }
+        disks
+    }
+
+    pub fn required_sizes(&self, default_disk: &str) -> BTreeMap<String, u64> {
+        let mut required = BTreeMap::new();
+        if self.reqpart {
+            required.insert(default_disk.to_string(), REQPART_SIZE);
+        }
+        for partition in &self.partitions {
+            *required
+                .entry(self.disk_of(partition, default_disk).to_string())
+                .or_insert(0) += partition.size;
+        }
+        for volume_group in &self.volume_groups {
+            let mut physical_size = 0;
+            let mut physical_disks = vec![];
+            for pv in &volume_group.physical_volumes {
+                if let Some(partition) = self.partition(pv) {
+                    physical_size += partition.size;
+                    physical_disks.push(self.disk_of(partition, default_disk));
+                } else if let Some(raid) = self.raids.iter().find(|x| &x.mount == pv) {
+                    let members: Vec<&Partition> = raid
+                        .members
+                        .iter()
+                        .filter_map(|x| self.partition(x))
+                        .collect();
+                    let smallest = members.iter().map(|x| x.size).min().unwrap_or(0);
+                    physical_size += smallest * raid.data_members();
+                    physical_disks.extend(members.iter().map(|x| self.disk_of(x, default_disk)));
+                }
+            }
+            let logical_size: u64 = volume_group
+                .logical_volumes
+                .iter()
+                .filter(|x| x.poolname.is_none())
+                .map(|x| x.size)
+                .sum();
+            let shortfall = logical_size.saturating_sub(physical_size);
+            if shortfall > 0 {
+                physical_disks.sort();
+                physical_disks.dedup();
+                for disk in physical_disks {
+                    *required.entry(disk.to_string()).or_insert(0) += shortfall;
+                }
+            }
+        }
+        required
+    }
+
+    fn check_raid_disks(&self, default_disk: &str) -> anyhow::Result<()> {
+        for raid in &self.raids {
+            let mut disks = HashSet::new();
+            for member in raid.members.iter().filter_map(|x| self.partition(x)) {
+                if !disks.insert(self.disk_of(member, default_disk)) {
+                    return Err(anyhow::anyhow!(
+                        "RAID device {} has more than one member on {}",
+                        raid.device,
+                        self.disk_of(member, default_disk)
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn encrypt_partitions(&mut self) {
+        let is_data = |mount: &str| mount != "/boot" && mount != "/boot/efi";
+        for partition in &mut self.partitions {
+            if is_data(&partition.mount) && !partition.mount.starts_with("raid.") {
+                partition.encrypted = true;
+            }
+        }
+        for raid in &mut self.raids {
+            if is_data(&raid.mount) {
+                raid.encrypted = true;
+            }
+        }
+    }
+
+    pub fn encrypt_logical_volumes(&mut self) -> anyhow::Result<()> {
+        let mut found = false;
+        for volume_group in &mut self.volume_groups {
+            for logical_volume in &mut volume_group.logical_volumes {
+                if !logical_volume.thinpool {
+                    logical_volume.encrypted = true;
+                    found = true;
+                }
+            }
+        }
+        if !found {
+            return Err(anyhow::anyhow!(
+                "Storage layout has no logical volumes to encrypt"
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn has_encryption(&self) -> bool {
+        self.partitions.iter().any(|x| x.encrypted)
+            || self.raids.iter().any(|x| x.encrypted)
+            || self
+                .volume_groups
+                .iter()
+                .any(|x| x.logical_volumes.iter().any(|y| y.encrypted))
+    }
+
+    fn check_encryption(&self, luks_options: Option<&str>) -> anyhow::Result<()> {
+        let mut encrypted_pvs: HashSet<&str> = self
+            .partitions
+            .iter()
+            .filter(|x| x.encrypted && x.mount.starts_with("pv."))
+            .map(|x| x.mount.as_str())
+            .collect();
+        encrypted_pvs.extend(
+            self.raids
+                .iter()
+                .filter(|x| x.encrypted && x.mount.starts_with("pv."))
+                .map(|x| x.mount.as_str()),
+        );
+        if let Some(member) = self
+            .partitions
+            .iter()
+            .find(|x| x.encrypted && x.mount.starts_with("raid."))
+        {
+            return Err(anyhow::anyhow!(
+                "RAID member {} cannot be encrypted, encrypt the RAID device instead",
+                member.mount
+            ));
+        }
+        for volume_group in &self.volume_groups {
+            for logical_volume in &volume_group.logical_volumes {
+                if logical_volume.thinpool && logical_volume.encrypted {
+                    return Err(anyhow::anyhow!(
+                        "Thin pool {} cannot be encrypted, encrypt its volumes instead",
+                        logical_volume.name
+                    ));
+                }
+                if logical_volume.encrypted
+                    && volume_group
+                        .physical_volumes
+                        .iter()
+                        .any(|x| encrypted_pvs.contains(x.as_str()))
+                {
+                    return Err(anyhow::anyhow!(
+                        "Logical volume {} is on an encrypted physical volume",
+                        logical_volume.name
+                    ));
+                }
+            }
+        }
+        if self.has_encryption() && luks_options.is_none() {
+            return Err(anyhow::anyhow!(
+                "Storage layout has encrypted volumes but no LUKS passphrase is given"
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn generate(
+        &self,
+        storage_device: &str,
+        filesystem: &str,
+        luks_options: Option<&str>,
+    ) -> anyhow::Result<String> {
+        self.validate()?;
+        self.check_raid_disks(storage_device)?;
+        self.check_encryption(luks_options)?;
+        let encryption = |encrypted: bool| {
+            if encrypted {
+                format!(" {}", luks_options.unwrap_or_default())
+            } else {
+                String::new()
+            }
+        };
+        let mut lines = vec![];
+        if self.reqpart {
+            lines.push("reqpart".to_string());
+        }
+        for partition in &self.partitions {
+            let fstype = partition
+                .fstype
+                .as_deref()
+                .unwrap_or_else(|| default_fstype(&partition.mount, filesystem));
+            let ondisk = partition.ondisk.as_deref().unwrap_or(storage_device);
+            lines.push(format!(
+                "part {} --fstype=\"{fstype}\" --ondisk={ondisk}{}{}",
+                partition.mount,
+                size_options(
+                    partition.size,
+                    partition.recommended,
+                    partition.grow,
+                    partition.maxsize
+                ),
+                encryption(partition.encrypted)
+            ));
+        }
+        for raid in &self.raids {
+            let fstype = raid
+                .fstype
+                .as_deref()
+                .unwrap_or_else(|| default_fstype(&raid.mount, filesystem));
+            lines.push(format!(
+                "raid {} --device={} --fstype=\"{fstype}\" --level=RAID{}{} {}",
+                raid.mount,
+                raid.device,
+                raid.level,
+                encryption(raid.encrypted),
+                raid.members.join(" ")
+            ));
+        }
+        for volume_group in &self.volume_groups {
+            let pesize = volume_group
+                .pesize
+                .map(|x| format!(" --pesize={x}"))
+                .unwrap_or_default();
+            lines.push(format!(
+                "volgroup {}{pesize} {}",
+                volume_group.name,
+                volume_group.physical_volumes.join(" ")
+            ));
+            for logical_volume in &volume_group.logical_volumes {
+                let fstype = logical_volume
+                    .fstype
+                    .as_deref()
+                    .unwrap_or_else(|| default_fstype(&logical_volume.mount, filesystem));
+                let mut line = if logical_volume.thinpool {
+                    format!(
+                        "logvol none --size={} --thinpool --name={} --vgname={}",
+                        logical_volume.size, logical_volume.name, volume_group.name
+                    )
+                } else {
+                    let size = if logical_volume.recommended {
+                        "--recommended".to_string()
+                    } else {
+                        format!("--size={}", logical_volume.size)
+                    };
+                    format!(
+                        "logvol {} --fstype=\"{fstype}\" {size} --name={} --vgname={}",
+                        logical_volume.mount, logical_volume.name, volume_group.name
+                    )
+                };
+                if let Some(poolname) = &logical_volume.poolname {
+                    line.push_str(&format!(" --thin --poolname={poolname}"));
+                }
+                if logical_volume.grow {
+                    line.push_str(" --grow");
+                }
+                if let Some(maxsize) = logical_volume.maxsize {
+                    line.push_str(&format!(" --maxsize={maxsize}"));
+                }
+                line.push_str(&encryption(logical_volume.encrypted));
+                lines.push(line);
+            }
+        }
+        for volume in &self.btrfs_volumes {
+            lines.push(format!(
+                "btrfs none --label={} {}",
+                volume.label,
+                volume.members.join(" ")
+            ));
+            for subvolume in &volume.subvolumes {
+                lines.push(format!(
+                    "btrfs {} --subvol --name={} LABEL={}",
+                    subvolume.mount, subvolume.name, volume.label
+                ));
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_layout() {
+        let layout = StorageLayout::load("testdata/layout.json").unwrap();
+        assert_eq!(
+            layout.generate("vda", "xfs", None).unwrap(),
+            r#"reqpart
+part /boot --fstype="xfs" --ondisk=vda --size=1024
+part pv.01 --fstype="lvmpv" --ondisk=vda --size=20480 --grow
+volgroup vg_db pv.01
+logvol swap --fstype="swap" --size=4096 --name=swap --vgname=vg_db
+logvol / --fstype="xfs" --size=10240 --name=root --vgname=vg_db
+logvol /var --fstype="xfs" --size=8192 --name=var --vgname=vg_db
+logvol /var/lib/pgsql --fstype="xfs" --size=20480 --name=pgsql --vgname=vg_db --grow"#
+        );
+    }
+
+    #[test]
+    fn test_preset_layouts() {
+        assert_eq!(
+            StorageLayout::thin_lvm()
+                .generate("vda", "xfs", None)
+                .unwrap(),
+            r#"reqpart
+part pv.116 --fstype="lvmpv" --ondisk=vda --size=15360 --grow
+part /boot --fstype="xfs" --ondisk=vda --size=1024
+volgroup almalinux --pesize=4096 pv.116
+logvol swap --fstype="swap" --size=4030 --name=swap --vgname=almalinux
+logvol none --size=10240 --thinpool --name=pool00 --vgname=almalinux --grow
+logvol / --fstype="xfs" --size=10240 --name=root --vgname=almalinux --thin --poolname=pool00"#
+        );
+        assert_eq!(
+            StorageLayout::plain()
+                .generate("vda", "ext4", None)
+                .unwrap(),
+            r#"reqpart
+part /boot --fstype="ext4" --ondisk=vda --size=1024
+part swap --fstype="swap" --ondisk=vda --size=4030
+part / --fstype="ext4" --ondisk=vda --size=10240 --grow"#
+        );
+        assert_eq!(
+            StorageLayout::btrfs()
+                .generate("vda", "btrfs", None)
+                .unwrap(),
+            r#"reqpart
+part /boot --fstype="ext4" --ondisk=vda --size=1024
+part swap --fstype="swap" --ondisk=vda --size=4030
+part btrfs.01 --fstype="btrfs" --ondisk=vda --size=10240 --grow
+btrfs none --label=root btrfs.01
+btrfs / --subvol --name=root LABEL=root
+btrfs /home --subvol --name=home LABEL=root"#
+        );
+    }
+
+    #[test]
+    fn test_encrypted_layout() {
+        let luks = Some(r#"--encrypted --passphrase="secret" --luks-version=luks2"#);
+        let mut layout = StorageLayout::default();
+        layout.encrypt_partitions();
+        assert_eq!(
+            layout.generate("vda", "xfs", luks).unwrap(),
+            r#"reqpart
+part pv.116 --fstype="lvmpv" --ondisk=vda --size=15360 --grow --encrypted --passphrase="secret" --luks-version=luks2
+part /boot --fstype="xfs" --ondisk=vda --size=1024
+volgroup almalinux --pesize=4096 pv.116
+logvol swap --fstype="swap" --size=4030 --name=swap --vgname=almalinux
+logvol / --fstype="xfs" --size=10240 --name=root --vgname=almalinux --grow"#
+        );
+        assert!(layout.generate("vda", "xfs", None).is_err());
+        layout.encrypt_logical_volumes().unwrap();
+        assert!(layout.generate("vda", "xfs", luks).is_err());
+
+        let mut layout = StorageLayout::thin_lvm();
+        layout.encrypt_logical_volumes().unwrap();
+        let generated = layout.generate("vda", "xfs", luks).unwrap();
+        assert!(generated.contains("--name=pool00 --vgname=almalinux --grow\n"));
+        assert!(generated.ends_with(
+            "--poolname=pool00 --encrypted --passphrase=\"secret\" --luks-version=luks2"
+        ));
+        assert!(StorageLayout::plain().encrypt_logical_volumes().is_err());
+    }
+
+    #[test]
+    fn test_swap_size() {
+        assert_eq!(recommended_swap_size(1024), 2048);
+        assert_eq!(recommended_swap_size(4096), 4096);
+        assert_eq!(recommended_swap_size(16384), 4096);
+
+        let mut layout = StorageLayout::default();
+        layout.apply_swap(SwapSize::Recommended).unwrap();
+        assert!(layout.generate("vda", "xfs", None).unwrap().contains(
+            "logvol swap --fstype=\"swap\" --recommended --name=swap --vgname=almalinux"
+        ));
+        layout.apply_swap(SwapSize::Fixed(2048)).unwrap();
+        assert_eq!(layout.required_sizes("vda")["vda"], 600 + 15360 + 1024);
+        layout.volume_groups[0].logical_volumes[1].size = 20480;
+        assert_eq!(
+            layout.required_sizes("vda")["vda"],
+            600 + 15360 + 1024 + 2048 + 20480 - 15360
+        );
+
+        let mut layout = StorageLayout::plain();
+        layout.apply_swap(SwapSize::None).unwrap();
+        assert!(!layout
+            .generate("vda", "xfs", None)
+            .unwrap()
+            .contains("swap"));
+        assert!(layout.apply_swap(SwapSize::Fixed(1024)).is_err());
+    }
+
+    #[test]
+    fn test_raid_layout() {
+        let layout = StorageLayout::raid1("vda", "vdb");
+        assert_eq!(
+            layout.generate("vda", "xfs", None).unwrap(),
+            r#"reqpart
+part raid.01 --fstype="mdmember" --ondisk=vda --size=1024
+part raid.02 --fstype="mdmember" --ondisk=vdb --size=1024
+part raid.11 --fstype="mdmember" --ondisk=vda --size=15360 --grow
+part raid.12 --fstype="mdmember" --ondisk=vdb --size=15360 --grow
+raid /boot --device=boot --fstype="xfs" --level=RAID1 raid.01 raid.02
+raid pv.116 --device=pv00 --fstype="lvmpv" --level=RAID1 raid.11 raid.12
+volgroup almalinux --pesize=4096 pv.116
+logvol swap --fstype="swap" --size=4030 --name=swap --vgname=almalinux
+logvol / --fstype="xfs" --size=10240 --name=root --vgname=almalinux --grow"#
+        );
+        assert_eq!(layout.disks("vda"), vec!["vda", "vdb"]);
+        let required = layout.required_sizes("vda");
+        assert_eq!(required["vda"], 600 + 1024 + 15360);
+        assert_eq!(required["vdb"], 1024 + 15360);
+
+        let mut encrypted = layout.clone();
+        encrypted.encrypt_partitions();
+        assert!(encrypted
+            .generate("vda", "xfs", Some("--encrypted"))
+            .unwrap()
+            .contains("raid pv.116 --device=pv00 --fstype=\"lvmpv\" --level=RAID1 --encrypted raid.11 raid.12"));
+
+        assert!(StorageLayout::raid1("vda", "vda")
+            .generate("vda", "xfs", None)
+            .is_err());
+        let mut invalid = layout.clone();
+        invalid.raids[0].members.pop();
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_layout() {
+        let mut layout = StorageLayout::default();
+        layout.volume_groups[0].physical_volumes = vec!["pv.999".to_string()];
+        assert!(layout.validate().is_err());
+
+        let mut layout = StorageLayout::default();
+        layout.volume_groups[0].logical_volumes[0].mount = "/boot".to_string();
+        assert!(layout.validate().is_err());
+
+        let mut layout = StorageLayout::thin_lvm();
+        layout.volume_groups[0].logical_volumes[2].poolname = Some("pool99".to_string());
+        assert!(layout.validate().is_err());
+
+        let mut layout = StorageLayout::btrfs();
+        layout.btrfs_volumes[0].members = vec!["btrfs.02".to_string()];
+        assert!(layout.validate().is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::scripts::{shell_quote, write_file};
+use crate::spec;
+
+const SUDOERS_DROPIN: &str = "/etc/sudoers.d/90-unattended-kvm-install";
+
+fn check_name(kind: &str, name: &str) -> anyhow::Result<()> {
+    let mut chars = name.chars();
+    let valid = matches!(chars.next(), Some(c) if c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_.-".contains(c));
+    if !valid {
+        return Err(anyhow::anyhow!("Invalid sudo {kind}: {name}"));
+    }
+    Ok(())
+}
+
+fn check_alias_name(name: &str) -> anyhow::Result<()> {
+    let mut chars = name.chars();
+    let valid = matches!(chars.next(), Some(c) if c.is_ascii_uppercase())
+        && chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        && name != "ALL";
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid command alias name: {name} (expected upper case letters, digits and _)"
+        ));
+    }
+    Ok(())
+}
+
+fn check_command(command: &str, aliases: &HashSet<&str>) -> anyhow::Result<String> {
+    if command == "ALL" || aliases.contains(command) {
+        return Ok(command.to_string());
+    }
+    if !command.starts_with('/') {
+        return Err(anyhow::anyhow!(
+            "Sudo command must be ALL, a command alias or an absolute path: {command}"
+        ));
+    }
+    if command.chars().any(|c| c.is_control() || c == '#') {
+        return Err(anyhow::anyhow!(
+            "Invalid character in sudo command: {command}"
+        ));
+    }
+    let mut escaped = String::new();
+    for c in command.chars() {
+        if "\\,:=".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    Ok(escaped)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct CommandAlias {
+    name: String,
+    commands: Vec<String>,
+}
+
+impl FromStr for CommandAlias {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut alias = CommandAlias::default();
+        for (key, value) in spec::parse_spec(s)? {
+            match key.as_str() {
+                "name" => alias.name = spec::required_value(&key, value)?,
+                "command" => alias.commands.push(spec::required_value(&key, value)?),
+                _ => return Err(anyhow::anyhow!("Unknown command alias option: {key}")),
+            }
+        }
+        check_alias_name(&alias.name)?;
+        if alias.commands.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Command alias {} has no command",
+                alias.name
+            ));
+        }
+        Ok(alias)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct SudoRule {
+    user: Option<String>,
+    group: Option<String>,
+    runas: Option<String>,
+    nopasswd: bool,
+    commands: Vec<String>,
+}
+
+impl FromStr for SudoRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rule = SudoRule::default();
+        for (key, value) in spec::parse_spec(s)? {
+            match key.as_str() {
+                "user" => rule.user = Some(spec::required_value(&key, value)?),
+                "group" => rule.group = Some(spec::required_value(&key, value)?),
+                "runas" => rule.runas = Some(spec::required_value(&key, value)?),
+                "nopasswd" => rule.nopasswd = spec::parse_bool(&key, value)?,
+                "command" => rule.commands.push(spec::required_value(&key, value)?),
+                _ => return Err(anyhow::anyhow!("Unknown sudo rule option: {key}")),
+            }
+        }
+        if rule.user.is_some() == rule.group.is_some() {
+            return Err(anyhow::anyhow!(
+                "Exactly one of user and group has to be set for a sudo rule"
+            ));
+        }
+        if rule.commands.is_empty() {
+            return Err(anyhow::anyhow!("Sudo rule has no command"));
+        }
+        Ok(rule)
+    }
+}
+
+impl SudoRule {
+    fn generate(&self, aliases: &HashSet<&str>) -> anyhow::Result<String> {
+        let who = match (&self.user, &self.group) {
+            (Some(user), _) => {
+                check_name("user", user)?;
+                user.to_string()
+            }
+            (_, Some(group)) => {
+                check_name("group", group)?;
+                format!("%{group}")
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Exactly one of user and group has to be set for a sudo rule"
+                ))
+            }
+        };
+        let runas = match self.runas.as_deref() {
+            None | Some("ALL") => "ALL".to_string(),
+            Some(runas) => {
+                check_name("runas user", runas)?;
+                runas.to_string()
+            }
+        };
+        let tag = if self.nopasswd { "NOPASSWD: " } else { "" };
+        let commands = self
+            .commands
+            .iter()
+            .map(|x| check_command(x, aliases))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(format!("{who} ALL=({runas}) {tag}{}", commands.join(", ")))
+    }
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SudoPolicy {
+    #[arg(long, help = "Allow the wheel group to use sudo without a password")]
+    #[serde(default)]
+    sudo_wheel_nopasswd: bool,
+    #[arg(
+        long,
+        help = "Sudo command alias, can be repeated (example: name=APP_CTL,command=\"/usr/bin/systemctl restart app\")"
+    )]
+    #[serde(default)]
+    sudo_command_alias: Vec<CommandAlias>,
+    #[arg(
+        long,
+        help = "Sudo rule, can be repeated (example: user=deploy,runas=root,nopasswd,command=APP_CTL)"
+    )]
+    #[serde(default)]
+    sudo_rule: Vec<SudoRule>,
+}
+
+impl SudoPolicy {
+    fn sudoers(&self) -> anyhow::Result<Option<String>> {
+        if !self.sudo_wheel_nopasswd && self.sudo_rule.is_empty() {
+            if !self.sudo_command_alias.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Sudo command aliases are given without rules"
+                ));
+            }
+            return Ok(None);
+        }
+
+        let mut lines = vec!["# Generated by unattended-kvm-install".to_string()];
+        let mut aliases = HashSet::new();
+        for alias in &self.sudo_command_alias {
+            if !aliases.insert(alias.name.as_str()) {
+                return Err(anyhow::anyhow!("Duplicated command alias: {}", alias.name));
+            }
+            let commands = alias
+                .commands
+                .iter()
+                .map(|x| check_command(x, &HashSet::new()))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            lines.push(format!(
+                "Cmnd_Alias {} = {}",
+                alias.name,
+                commands.join(", ")
+            ));
+        }
+        if self.sudo_wheel_nopasswd {
+            lines.push("%wheel ALL=(ALL) NOPASSWD: ALL".to_string());
+        }
+        for rule in &self.sudo_rule {
+            lines.push(rule.generate(&aliases)?);
+        }
+        Ok(Some(lines.join("\n") + "\n"))
+    }
+
+    /// `%post` body installing the sudoers drop-in, or `None` without sudo options.
+    pub fn generate(&self) -> anyhow::Result<Option<String>> {
+        let Some(sudoers) = self.sudoers()? else {
+            return Ok(None);
+        };
+        let dropin = shell_quote(SUDOERS_DROPIN);
+        Ok(Some(format!(
+            "{}\nif ! visudo -cf {dropin}; then\n    echo \"Invalid sudoers drop-in, removing {SUDOERS_DROPIN}\" >&2\n    rm -f {dropin}\nfi",
+            write_file(
+                SUDOERS_DROPIN,
+                sudoers.as_bytes(),
+                Some("0440"),
+                Some("root:root")
+            )
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sudo_policy() {
+        let policy = SudoPolicy {
+            sudo_wheel_nopasswd: true,
+            sudo_command_alias: vec![
+                r#"name=APP_CTL,command="/usr/bin/systemctl restart app",command="/usr/bin/journalctl -u app --since=today""#
+                    .parse()
+                    .unwrap(),
+            ],
+            sudo_rule: vec![
+                "user=deploy,runas=root,nopasswd,command=APP_CTL"
+                    .parse()
+                    .unwrap(),
+                "group=dba,runas=postgres,command=/usr/bin/psql"
+                    .parse()
+                    .unwrap(),
+            ],
+        };
+        assert_eq!(
+            policy.sudoers().unwrap().unwrap(),
+            r#"# Generated by unattended-kvm-install
+Cmnd_Alias APP_CTL = /usr/bin/systemctl restart app, /usr/bin/journalctl -u app --since\=today
+%wheel ALL=(ALL) NOPASSWD: ALL
+deploy ALL=(root) NOPASSWD: APP_CTL
+%dba ALL=(postgres) /usr/bin/psql
+"#
+        );
+        let generated = policy.generate().unwrap().unwrap();
+        assert!(generated.contains("chmod 0440 '/etc/sudoers.d/90-unattended-kvm-install'\n"));
+        assert!(generated.contains("visudo -cf '/etc/sudoers.d/90-unattended-kvm-install'"));
+        assert_eq!(SudoPolicy::default().generate().unwrap(), None);
+
+        let mut invalid = policy.clone();
+        invalid.sudo_rule = vec!["user=deploy,command=OTHER_CTL".parse().unwrap()];
+        assert!(invalid.generate().is_err());
+        let mut invalid = policy.clone();
+        invalid.sudo_rule = vec!["user=Deploy!,command=ALL".parse().unwrap()];
+        assert!(invalid.generate().is_err());
+        let mut invalid = policy.clone();
+        invalid.sudo_rule = vec!["group=ops,command=/bin/sh # comment".parse().unwrap()];
+        assert!(invalid.generate().is_err());
+        let mut invalid = policy.clone();
+        invalid
+            .sudo_command_alias
+            .push("name=APP_CTL,command=/bin/true".parse().unwrap());
+        assert!(invalid.generate().is_err());
+        assert!("name=app_ctl,command=/bin/true"
+            .parse::<CommandAlias>()
+            .is_err());
+        assert!("user=a,group=b,command=ALL".parse::<SudoRule>().is_err());
+        assert!("user=a".parse::<SudoRule>().is_err());
+        let mut invalid = policy.clone();
+        invalid.sudo_rule = vec![SudoRule {
+            commands: vec!["ALL".to_string()],
+            ..Default::default()
+        }];
+        assert!(invalid.generate().is_err());
+    }
+}
@@ -0,0 +1,3 @@
+#!/bin/bash
+sed -i 's/^pool .*/server ntp.example.com iburst/' /etc/chrony.conf
+systemctl disable kdump.service